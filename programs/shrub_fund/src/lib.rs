@@ -3,25 +3,72 @@
 
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token_interface::{self, TokenAccount as InterfaceTokenAccount, TokenInterface, Transfer as InterfaceTransfer},
+    associated_token::get_associated_token_address_with_program_id,
+    token_2022::spl_token_2022::{
+        extension::{transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions},
+        state::Account as Token2022Account,
+    },
+    token_interface::{self, Burn as InterfaceBurn, Mint as InterfaceMint, MintTo as InterfaceMintTo, Token2022, TokenAccount as InterfaceTokenAccount, TokenInterface, Transfer as InterfaceTransfer},
 };
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
 
 declare_id!("GYe1hhxHhojNy5LfTddD79BdHCnsYC2dsD8KMrKn1se6");
 
-const GARDENER_PUBKEY: Pubkey = pubkey!("5yG2kXTKXQFX9GsvsvNBNjFrLz9smuq8yUJRZgk3ZWu8");
-const TRADING_WALLET: Pubkey = pubkey!("9Ufh1tSTYzSjwMTAczsPoRhtTKqwUvoVywGqPjDd9cP5");
-const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
-
-// Registry system constants
+// Registry system hard limits (FundConfig values must stay within these)
 const MAX_USERS_PER_REGISTRY: u64 = 100_000;
 const MAX_REGISTRIES: u64 = 100;
 
+// Basis points denominator (10000 bps = 100%)
+const BPS_DENOMINATOR: u64 = 10_000;
+
+// Management fee accrual period (annual rate)
+const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+// remaining_accounts per ticket in settle_matured_unstakes:
+// [UnstakeTicket, UserShare, UserRegistry, destination token account, user wallet]
+const SETTLE_ACCOUNTS_PER_TICKET: usize = 5;
+
+// Emergency pause flags (FundConfig.pause_flags bitmask)
+const PAUSE_STAKE: u8 = 1 << 0;
+const PAUSE_INITIATE_UNSTAKE: u8 = 1 << 1;
+const PAUSE_COMPLETE_UNSTAKE: u8 = 1 << 2;
+const PAUSE_NAV_UPDATE: u8 = 1 << 3;
+const PAUSE_COMMISSION: u8 = 1 << 4;
+const PAUSE_TRANSFER: u8 = 1 << 5;
+const PAUSE_INSTANT_UNSTAKE: u8 = 1 << 6;
+const PAUSE_ALL: u8 = PAUSE_STAKE | PAUSE_INITIATE_UNSTAKE | PAUSE_COMPLETE_UNSTAKE | PAUSE_NAV_UPDATE | PAUSE_COMMISSION | PAUSE_TRANSFER | PAUSE_INSTANT_UNSTAKE;
+
 // Precision for NAV calculations (6 decimals to match USDC)
 const NAV_PRECISION: u64 = 1_000_000;
 
+// Share token decimals (shares are priced in NAV_PRECISION units)
+const SHARE_DECIMALS: u8 = 6;
+
+// Transfer hook extra accounts: sender/receiver UserShare, sender/receiver UserRegistry, FundConfig, FundPool,
+// receiver AllowlistEntry
+const SHARE_HOOK_EXTRA_ACCOUNTS: usize = 7;
+// Byte offsets used by the hook to derive PDAs from account data
+const TOKEN_ACCOUNT_OWNER_OFFSET: u8 = 32;          // spl token Account.owner
+const USER_SHARE_REGISTRY_ID_OFFSET: u8 = 8 + 32 + 8 + 8; // discriminator + user + shares + stake_timestamp
+
 // NAV History configuration (84 entries for 7 days at 2-hour intervals)
 const MAX_NAV_HISTORY: usize = 84;
 
+// Upper bound on registered NAV reporters (and submissions per epoch)
+const MAX_NAV_REPORTERS: usize = 10;
+
+// remaining_accounts per holding in compute_nav:
+// [trading wallet token account, PriceFeedConfig, price account]
+const COMPUTE_NAV_ACCOUNTS_PER_HOLDING: usize = 3;
+
+// Pyth pull-oracle receiver program and PriceUpdateV2 account layout
+const PYTH_RECEIVER_PROGRAM_ID: Pubkey = anchor_lang::solana_program::pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+const PYTH_PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const PYTH_VERIFICATION_LEVEL_OFFSET: usize = 8 + 32; // discriminator + write_authority
+const PYTH_VERIFICATION_FULL: u8 = 1;
+
 // Helper functions
 fn calculate_optimized_nav_per_share(fund_pool: &Account<FundPool>) -> Result<u64> {
     if fund_pool.total_shares == 0 {
@@ -76,8 +123,322 @@ fn calculate_usdc_for_shares(fund_pool: &Account<FundPool>, shares: u64) -> Resu
     Ok(usdc_amount)
 }
 
+// Value of `shares` at REAL NAV (used for position limits)
+fn calculate_real_value_of_shares(fund_pool: &Account<FundPool>, shares: u64) -> Result<u64> {
+    let real_nav_per_share = calculate_real_nav_per_share(fund_pool)?;
+
+    let usdc_amount = (shares as u128)
+        .checked_mul(real_nav_per_share as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(NAV_PRECISION as u128)
+        .ok_or(ErrorCode::MathOverflow)? as u64;
+
+    Ok(usdc_amount)
+}
+
+// Per-wallet position cap (0 = unlimited)
+fn require_position_within_limit(config: &FundConfig, fund_pool: &Account<FundPool>, shares_after: u64) -> Result<()> {
+    if config.max_position_usdc == 0 {
+        return Ok(());
+    }
+    let position_value = calculate_real_value_of_shares(fund_pool, shares_after)?;
+    require!(position_value <= config.max_position_usdc, ErrorCode::PositionLimitExceeded);
+    Ok(())
+}
+
+// Shares moved into a wallet with no active position must be worth at least the minimum deposit
+fn require_opening_transfer_above_minimum(config: &FundConfig, fund_pool: &Account<FundPool>, shares: u64) -> Result<()> {
+    let transferred_value = calculate_real_value_of_shares(fund_pool, shares)?;
+    require!(transferred_value >= config.min_deposit_usdc, ErrorCode::DepositBelowMinimum);
+    Ok(())
+}
+
+// USDC originally paid for `shares` at a weighted-average cost per share
+fn calculate_cost_of_shares(shares: u64, cost_basis_per_share: u64) -> Result<u64> {
+    let cost = (shares as u128)
+        .checked_mul(cost_basis_per_share as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(NAV_PRECISION as u128)
+        .ok_or(ErrorCode::MathOverflow)? as u64;
+    
+    Ok(cost)
+}
+
+// Weighted-average cost per share after buying `new_shares` for `usdc_paid`
+fn calculate_weighted_cost_basis(existing_shares: u64, existing_cost_basis_per_share: u64, new_shares: u64, usdc_paid: u64) -> Result<u64> {
+    let existing_cost = calculate_cost_of_shares(existing_shares, existing_cost_basis_per_share)?;
+    let total_cost = existing_cost.checked_add(usdc_paid).ok_or(ErrorCode::MathOverflow)?;
+    let total_shares = existing_shares.checked_add(new_shares).ok_or(ErrorCode::MathOverflow)?;
+    
+    let cost_basis_per_share = (total_cost as u128)
+        .checked_mul(NAV_PRECISION as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(total_shares as u128)
+        .ok_or(ErrorCode::MathOverflow)? as u64;
+    
+    Ok(cost_basis_per_share)
+}
+
+// Move active shares between positions; the recipient takes on the sender's cost basis
+fn move_shares_with_cost_basis(sender_share: &mut UserShare, recipient_share: &mut UserShare, shares: u64) -> Result<()> {
+    let transferred_cost_basis = calculate_cost_of_shares(shares, sender_share.cost_basis_per_share)?;
+    recipient_share.cost_basis_per_share = calculate_weighted_cost_basis(
+        recipient_share.shares,
+        recipient_share.cost_basis_per_share,
+        shares,
+        transferred_cost_basis,
+    )?;
+    recipient_share.shares = recipient_share.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+    sender_share.shares = sender_share.shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+// Mint share tokens with the fund_pool PDA as mint authority (keeps supply == total_shares)
+fn mint_share_tokens<'info>(
+    share_token_program: &Interface<'info, TokenInterface>,
+    share_mint: &InterfaceAccount<'info, InterfaceMint>,
+    to: &InterfaceAccount<'info, InterfaceTokenAccount>,
+    fund_pool: &Account<'info, FundPool>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    
+    let fund_pool_seeds = &[
+        b"optimized_fund_pool_v2".as_ref(),
+        &[fund_pool.bump],
+    ];
+    token_interface::mint_to(
+        CpiContext::new_with_signer(
+            share_token_program.to_account_info(),
+            InterfaceMintTo {
+                mint: share_mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: fund_pool.to_account_info(),
+            },
+            &[fund_pool_seeds],
+        ),
+        amount,
+    )
+}
+
+// Extra accounts Token-2022 resolves for every share transfer (indices 0-4 are
+// source, mint, destination, owner and the meta list itself)
+fn share_hook_extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![
+        // 5: sender UserShare = ["user_share_v2", source.owner]
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: b"user_share_v2".to_vec() },
+                Seed::AccountData { account_index: 0, data_index: TOKEN_ACCOUNT_OWNER_OFFSET, length: 32 },
+            ],
+            false,
+            true,
+        )?,
+        // 6: receiver UserShare = ["user_share_v2", destination.owner]
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: b"user_share_v2".to_vec() },
+                Seed::AccountData { account_index: 2, data_index: TOKEN_ACCOUNT_OWNER_OFFSET, length: 32 },
+            ],
+            false,
+            true,
+        )?,
+        // 7: sender UserRegistry = ["user_registry_v2", sender_share.registry_id]
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: b"user_registry_v2".to_vec() },
+                Seed::AccountData { account_index: 5, data_index: USER_SHARE_REGISTRY_ID_OFFSET, length: 8 },
+            ],
+            false,
+            true,
+        )?,
+        // 8: receiver UserRegistry = ["user_registry_v2", receiver_share.registry_id]
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: b"user_registry_v2".to_vec() },
+                Seed::AccountData { account_index: 6, data_index: USER_SHARE_REGISTRY_ID_OFFSET, length: 8 },
+            ],
+            false,
+            true,
+        )?,
+        // 9: FundConfig = ["fund_config_v2"]
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"fund_config_v2".to_vec() }],
+            false,
+            false,
+        )?,
+        // 10: FundPool = ["optimized_fund_pool_v2"]
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"optimized_fund_pool_v2".to_vec() }],
+            false,
+            true,
+        )?,
+        // 11: receiver AllowlistEntry = ["allowlist_v2", destination.owner] (may not exist)
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal { bytes: b"allowlist_v2".to_vec() },
+                Seed::AccountData { account_index: 2, data_index: TOKEN_ACCOUNT_OWNER_OFFSET, length: 32 },
+            ],
+            false,
+            false,
+        )?,
+    ])
+}
+
+// Allowlist check for an entry PDA that may never have been created
+fn require_allowlisted(allowlist_entry: &AccountInfo) -> Result<()> {
+    require!(
+        *allowlist_entry.owner == crate::ID && !allowlist_entry.data_is_empty(),
+        ErrorCode::NotAllowlisted
+    );
+    let entry = AllowlistEntry::try_deserialize(&mut &allowlist_entry.try_borrow_data()?[..])?;
+    require!(!entry.revoked, ErrorCode::AllowlistRevoked);
+    Ok(())
+}
+
+// Reject hook invocations that are not part of a live Token-2022 transfer
+fn check_is_transferring(source_token: &AccountInfo) -> Result<()> {
+    let data = source_token.try_borrow_data()?;
+    let account = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+    let extension = account.get_extension::<TransferHookAccount>()?;
+    require!(bool::from(extension.transferring), ErrorCode::NotTransferring);
+    Ok(())
+}
+
+// Dilutive fee shares for `elapsed` seconds of the annual management fee.
+// Fee fraction f = bps * elapsed / (10000 * year); minting S * f / (1 - f) shares
+// leaves existing holders with exactly (1 - f) of the fund.
+fn calculate_management_fee_shares(total_shares: u64, management_fee_bps: u16, elapsed: i64) -> Result<u64> {
+    if elapsed <= 0 || management_fee_bps == 0 || total_shares == 0 {
+        return Ok(0);
+    }
+    
+    let fee_numerator = (management_fee_bps as u128)
+        .checked_mul(elapsed as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let fee_denominator = (BPS_DENOMINATOR as u128)
+        .checked_mul(SECONDS_PER_YEAR as u128)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_sub(fee_numerator)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(fee_denominator > 0, ErrorCode::MathOverflow);
+    
+    let fee_shares = (total_shares as u128)
+        .checked_mul(fee_numerator)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(fee_denominator)
+        .ok_or(ErrorCode::MathOverflow)? as u64;
+    
+    Ok(fee_shares)
+}
+
+// Accrue the annual management fee by minting dilutive fee shares to the gardener
+fn accrue_management_fee(
+    fund_pool: &mut Account<FundPool>,
+    gardener_share: &mut Account<UserShare>,
+    gardener: Pubkey,
+    management_fee_bps: u16,
+    current_time: i64,
+) -> Result<u64> {
+    let elapsed = current_time.saturating_sub(fund_pool.last_fee_accrual_timestamp);
+    let fee_shares = calculate_management_fee_shares(fund_pool.total_shares, management_fee_bps, elapsed)?;
+
+    // Periods too short to mint a whole fee share keep accruing, so frequent calls cannot
+    // round the fee away; an empty fund or a disabled fee has nothing to carry forward
+    if fee_shares > 0 || fund_pool.total_shares == 0 || management_fee_bps == 0 {
+        fund_pool.last_fee_accrual_timestamp = current_time;
+    }
+    if fee_shares == 0 {
+        return Ok(0);
+    }
+    
+    if gardener_share.user == Pubkey::default() {
+        gardener_share.user = gardener;
+        gardener_share.shares = 0;
+        gardener_share.cost_basis_per_share = 0;
+        gardener_share.stake_timestamp = current_time;
+        fund_pool.total_users = fund_pool.total_users
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+    
+    // Fee shares are acquired at zero cost
+    gardener_share.cost_basis_per_share = calculate_weighted_cost_basis(
+        gardener_share.shares,
+        gardener_share.cost_basis_per_share,
+        fee_shares,
+        0,
+    )?;
+    gardener_share.shares = gardener_share.shares.checked_add(fee_shares).ok_or(ErrorCode::MathOverflow)?;
+    fund_pool.total_shares = fund_pool.total_shares.checked_add(fee_shares).ok_or(ErrorCode::MathOverflow)?;
+    
+    msg!("Management fee accrued: {} shares minted to gardener for {}s at {} bps/year",
+         fee_shares, elapsed, management_fee_bps);
+    Ok(fee_shares)
+}
+
+// Accrue the management fee and mint the resulting fee shares to the gardener's token account.
+// The gardener joins the registry like any other holder the first time fee shares are minted.
+fn settle_management_fee<'info>(
+    fund_pool: &mut Account<'info, FundPool>,
+    config: &FundConfig,
+    fee_settlement: &mut FeeSettlement<'info>,
+    current_time: i64,
+) -> Result<u64> {
+    // FeeSettlement derives the gardener's accounts from the share token account owner
+    require_keys_eq!(
+        fee_settlement.gardener_share_token_account.owner,
+        config.gardener,
+        ErrorCode::UnauthorizedGardener
+    );
+
+    let gardener_share = &mut fee_settlement.gardener_share;
+    let is_new_gardener = gardener_share.user == Pubkey::default();
+    let fee_shares = accrue_management_fee(
+        fund_pool,
+        gardener_share,
+        config.gardener,
+        config.management_fee_bps,
+        current_time,
+    )?;
+    mint_share_tokens(
+        &fee_settlement.share_token_program,
+        &fee_settlement.share_mint,
+        &fee_settlement.gardener_share_token_account,
+        fund_pool,
+        fee_shares,
+    )?;
+
+    if fee_shares == 0 {
+        return Ok(0);
+    }
+    let gardener_share = &mut fee_settlement.gardener_share;
+    if is_new_gardener {
+        let registry_directory = fee_settlement.registry_directory.as_mut().ok_or(ErrorCode::UserNotInRegistry)?;
+        let user_registry = fee_settlement.user_registry.as_mut().ok_or(ErrorCode::UserNotInRegistry)?;
+        let (registry_id, registry_index) = registry_add_user(
+            registry_directory,
+            user_registry,
+            gardener_share.user,
+            gardener_share.shares,
+            gardener_share.cost_basis_per_share,
+            gardener_share.stake_timestamp,
+            config.max_users_per_registry,
+            &fee_settlement.payer.to_account_info(),
+            &fee_settlement.system_program.to_account_info(),
+        )?;
+        gardener_share.registry_id = registry_id;
+        gardener_share.registry_index = registry_index;
+    } else if let Some(user_registry) = fee_settlement.user_registry.as_mut() {
+        registry_sync_position(user_registry, gardener_share);
+    }
+    Ok(fee_shares)
+}
+
 // Calculate average NAV from history entries
-fn calculate_average_nav(nav_history: &Vec<NavHistoryEntry>) -> Result<u64> {
+fn calculate_average_nav(nav_history: &[NavHistoryEntry]) -> Result<u64> {
     if nav_history.is_empty() {
         return Err(ErrorCode::EmptyNavHistory.into());
     }
@@ -88,7 +449,7 @@ fn calculate_average_nav(nav_history: &Vec<NavHistoryEntry>) -> Result<u64> {
     Ok(average as u64)
 }
 
-fn update_nav_history(nav_history: &mut Account<NavHistory>, new_real_nav: u64, current_time: i64) -> Result<Vec<u64>> {
+fn update_nav_history(nav_history: &mut Account<NavHistory>, new_real_nav: u64, current_time: i64, nav_window_seconds: i64) -> Result<()> {
     // Add new entry
     let new_entry = NavHistoryEntry {
         timestamp: current_time,
@@ -97,32 +458,385 @@ fn update_nav_history(nav_history: &mut Account<NavHistory>, new_real_nav: u64,
     
     nav_history.entries.push(new_entry);
     
-    // Remove entries older than 7 days (for 2-hour updates, max 84 entries)
-    let seven_days_ago = current_time - (7 * 24 * 60 * 60);
-    nav_history.entries.retain(|entry| entry.timestamp >= seven_days_ago);
+    // Remove entries older than the configured window (7 days at 2-hour updates = 84 entries)
+    let window_start = current_time - nav_window_seconds;
+    nav_history.entries.retain(|entry| entry.timestamp >= window_start);
     
     // If history is too long, remove oldest entries
     while nav_history.entries.len() > MAX_NAV_HISTORY {
         nav_history.entries.remove(0);
     }
     
-    // Return all real_nav values for averaging
-    let real_navs: Vec<u64> = nav_history.entries.iter().map(|entry| entry.real_nav).collect();
-    Ok(real_navs)
+    Ok(())
+}
+
+// Redemption gate: each window accepts redemptions up to redemption_gate_bps of real NAV
+// (snapshotted when the window opens). Requests beyond that queue in arrival order and are
+// released in later windows. Returns the earliest time the request may start its notice period
+// and the start of the window it was released in (0 when the gate is off).
+fn apply_redemption_gate(fund_pool: &mut FundPool, config: &FundConfig, usdc_value: u64, current_time: i64) -> Result<(i64, i64)> {
+    if config.redemption_gate_bps == 0 {
+        return Ok((current_time, 0));
+    }
+    let window = config.redemption_window_seconds;
+
+    // Roll forward to the window containing current_time, draining one capacity per elapsed window
+    let window_end = fund_pool.redemption_window_start.checked_add(window).ok_or(ErrorCode::MathOverflow)?;
+    if current_time >= window_end {
+        let windows_elapsed = (current_time - fund_pool.redemption_window_start) / window;
+        fund_pool.redemption_window_queued = fund_pool.redemption_window_queued
+            .saturating_sub(fund_pool.redemption_window_capacity.saturating_mul(windows_elapsed as u64));
+        fund_pool.redemption_window_start = fund_pool.redemption_window_start
+            .checked_add(windows_elapsed.checked_mul(window).ok_or(ErrorCode::MathOverflow)?)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.redemption_window_capacity = ((fund_pool.real_nav as u128)
+            .checked_mul(config.redemption_gate_bps as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / BPS_DENOMINATOR as u128) as u64;
+    }
+    let capacity = fund_pool.redemption_window_capacity.max(1);
+
+    // Position of this request's last dollar in the queue decides its release window
+    let queued = fund_pool.redemption_window_queued
+        .checked_add(usdc_value)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let windows_ahead = (queued.saturating_sub(1) / capacity) as i64;
+    fund_pool.redemption_window_queued = queued;
+
+    let release_window_start = fund_pool.redemption_window_start
+        .checked_add(windows_ahead.checked_mul(window).ok_or(ErrorCode::MathOverflow)?)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok((release_window_start.max(current_time), release_window_start))
+}
+
+// Reprice the fund to a new portfolio value: real NAV moves immediately, optimized NAV
+// follows the one-directional averaged rule. Returns the new optimized NAV.
+fn apply_portfolio_value(
+    fund_pool: &mut FundPool,
+    nav_history: &mut Account<NavHistory>,
+    pending_cashout_pool: &PendingCashoutPool,
+    new_portfolio_value: u64,
+    current_time: i64,
+    nav_window_seconds: i64,
+) -> Result<u64> {
+    let old_optimized_nav = fund_pool.optimized_nav;
+    
+    // Always update real NAV immediately
+    fund_pool.real_nav = new_portfolio_value;
+    
+    // Record the new value and average over the history window
+    update_nav_history(nav_history, new_portfolio_value, current_time, nav_window_seconds)?;
+    let calculated_optimized_nav = calculate_average_nav(&nav_history.entries)?;
+    
+    // Apply one-directional averaged NAV system logic
+    let final_optimized_nav = if new_portfolio_value < old_optimized_nav {
+        // NAV decrease: apply immediately
+        new_portfolio_value
+    } else {
+        // NAV increase: use averaged NAV
+        calculated_optimized_nav
+    };
+    
+    fund_pool.optimized_nav = final_optimized_nav;
+    fund_pool.last_nav_update_timestamp = current_time;
+
+    // Calculate total pending cashout for alert
+    let total_pending: u64 = pending_cashout_pool.users.iter()
+        .map(|u| u.pending_usdc_cashout)
+        .sum();
+    fund_pool.pending_cashout = total_pending;
+
+    let uncovered = total_pending.saturating_sub(fund_pool.pending_cashout_funded);
+    if uncovered > 0 {
+        msg!("ALERT: {} USDC needed for pending cashouts ({} USDC already funded)",
+             uncovered, fund_pool.pending_cashout_funded);
+    }
+
+    Ok(final_optimized_nav)
+}
+
+// Guarded NAV update: enforces the minimum interval since the last NavHistory entry and
+// parks moves larger than max_nav_change_bps for the NAV confirmer, replacing any value
+// already parked. In-band updates leave a parked value for the confirmer to decide on.
+// Returns the new optimized NAV, or None when the value is held for confirmation.
+fn apply_portfolio_value_guarded(
+    fund_pool: &mut FundPool,
+    nav_history: &mut Account<NavHistory>,
+    pending_cashout_pool: &PendingCashoutPool,
+    config: &FundConfig,
+    new_portfolio_value: u64,
+    current_time: i64,
+) -> Result<Option<u64>> {
+    if let Some(last_entry) = nav_history.entries.last() {
+        let next_allowed = last_entry.timestamp
+            .checked_add(config.min_nav_update_interval_seconds)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(current_time >= next_allowed, ErrorCode::UpdateTooFrequent);
+    }
+
+    if config.max_nav_change_bps > 0
+        && fund_pool.real_nav > 0
+        && !is_within_tolerance(new_portfolio_value, fund_pool.real_nav, config.max_nav_change_bps)
+    {
+        fund_pool.pending_nav_value = new_portfolio_value;
+        fund_pool.pending_nav_timestamp = current_time;
+        msg!("NAV move from {} to {} USDC exceeds {} bps - awaiting confirmation",
+             fund_pool.real_nav, new_portfolio_value, config.max_nav_change_bps);
+        return Ok(None);
+    }
+
+    let final_optimized_nav = apply_portfolio_value(
+        fund_pool,
+        nav_history,
+        pending_cashout_pool,
+        new_portfolio_value,
+        current_time,
+        config.nav_window_seconds,
+    )?;
+    Ok(Some(final_optimized_nav))
+}
+
+// Refuse to price deposits and withdrawals off a NAV older than max_nav_staleness_seconds
+fn require_fresh_nav(fund_pool: &FundPool, config: &FundConfig, current_time: i64) -> Result<()> {
+    if config.max_nav_staleness_seconds == 0 {
+        return Ok(());
+    }
+    let stale_after = fund_pool.last_nav_update_timestamp
+        .checked_add(config.max_nav_staleness_seconds)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(current_time <= stale_after, ErrorCode::StaleNav);
+    Ok(())
+}
+
+// Median of reported values (mean of the two middle values for an even count)
+fn calculate_median(values: &mut [u64]) -> Result<u64> {
+    if values.is_empty() {
+        return Err(ErrorCode::NavQuorumNotReached.into());
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        return Ok(values[mid]);
+    }
+    Ok(((values[mid - 1] as u128 + values[mid] as u128) / 2) as u64)
+}
+
+// True if `value` is within `tolerance_bps` of `reference`
+fn is_within_tolerance(value: u64, reference: u64, tolerance_bps: u16) -> bool {
+    let deviation = value.abs_diff(reference) as u128;
+    deviation * BPS_DENOMINATOR as u128 <= reference as u128 * tolerance_bps as u128
+}
+
+fn require_not_paused(config: &FundConfig, flag: u8) -> Result<()> {
+    require!(config.pause_flags & flag == 0, ErrorCode::FundPaused);
+    Ok(())
+}
+
+fn validate_fund_config_params(params: &FundConfigParams) -> Result<()> {
+    require!(params.unstake_lockup_seconds >= 0, ErrorCode::InvalidConfig);
+    require!(params.min_holding_seconds >= 0, ErrorCode::InvalidConfig);
+    require!(params.commission_bps as u64 <= BPS_DENOMINATOR, ErrorCode::InvalidConfig);
+    require!(params.commission_period_seconds > 0, ErrorCode::InvalidConfig);
+    require!((params.management_fee_bps as u64) < BPS_DENOMINATOR, ErrorCode::InvalidConfig);
+    require!((params.exit_fee_bps as u64) < BPS_DENOMINATOR, ErrorCode::InvalidConfig);
+    require!(params.nav_window_seconds > 0, ErrorCode::InvalidConfig);
+    require!(params.redemption_gate_bps as u64 <= BPS_DENOMINATOR, ErrorCode::InvalidConfig);
+    require!(params.redemption_window_seconds > 0, ErrorCode::InvalidConfig);
+    require!(params.nav_quorum as usize <= MAX_NAV_REPORTERS, ErrorCode::InvalidConfig);
+    require!(params.nav_tolerance_bps as u64 <= BPS_DENOMINATOR, ErrorCode::InvalidConfig);
+    require!(params.min_nav_update_interval_seconds >= 0, ErrorCode::InvalidConfig);
+    require!(params.max_nav_staleness_seconds >= 0, ErrorCode::InvalidConfig);
+    require!(params.oracle_max_age_seconds > 0, ErrorCode::InvalidConfig);
+    require!(
+        params.max_users_per_registry > 0 && params.max_users_per_registry <= MAX_USERS_PER_REGISTRY,
+        ErrorCode::InvalidConfig
+    );
+    require!(
+        params.max_registries > 0 && params.max_registries <= MAX_REGISTRIES,
+        ErrorCode::InvalidConfig
+    );
+    Ok(())
+}
+
+fn apply_fund_config_params(config: &mut FundConfig, params: &FundConfigParams) {
+    config.unstake_lockup_seconds = params.unstake_lockup_seconds;
+    config.min_holding_seconds = params.min_holding_seconds;
+    config.commission_bps = params.commission_bps;
+    config.commission_period_seconds = params.commission_period_seconds;
+    config.management_fee_bps = params.management_fee_bps;
+    config.exit_fee_bps = params.exit_fee_bps;
+    config.nav_window_seconds = params.nav_window_seconds;
+    config.redemption_gate_bps = params.redemption_gate_bps;
+    config.redemption_window_seconds = params.redemption_window_seconds;
+    config.max_tvl_usdc = params.max_tvl_usdc;
+    config.min_deposit_usdc = params.min_deposit_usdc;
+    config.max_position_usdc = params.max_position_usdc;
+    config.allowlist_required = params.allowlist_required;
+    config.nav_quorum = params.nav_quorum;
+    config.nav_tolerance_bps = params.nav_tolerance_bps;
+    config.min_nav_update_interval_seconds = params.min_nav_update_interval_seconds;
+    config.max_nav_change_bps = params.max_nav_change_bps;
+    config.max_nav_staleness_seconds = params.max_nav_staleness_seconds;
+    config.oracle_max_age_seconds = params.oracle_max_age_seconds;
+    config.max_users_per_registry = params.max_users_per_registry;
+    config.max_registries = params.max_registries;
 }
 
 // Registry helper functions
+// Fold a new unstake ticket into the registry's aggregate unstake fields
+fn registry_add_pending_unstake(user_info: &mut UserInfo, shares: u64, usdc_value: u64, timestamp: i64) -> Result<()> {
+    let pending_shares = user_info.unstake_shares.unwrap_or(0)
+        .checked_add(shares)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let pending_usdc = user_info.unstake_usdc_value.unwrap_or(0)
+        .checked_add(usdc_value)
+        .ok_or(ErrorCode::MathOverflow)?;
+    
+    user_info.unstake_initialized_timestamp = Some(timestamp);
+    user_info.unstake_shares = Some(pending_shares);
+    user_info.unstake_usdc_value = Some(pending_usdc);
+    Ok(())
+}
+
+// Remove a settled or cancelled ticket; fields reset to none once no tickets remain
+fn registry_remove_pending_unstake(user_info: &mut UserInfo, shares: u64, usdc_value: u64, pending_tickets: u64) {
+    if pending_tickets == 0 {
+        user_info.unstake_initialized_timestamp = None;
+        user_info.unstake_shares = None;
+        user_info.unstake_usdc_value = None;
+    } else {
+        user_info.unstake_shares = Some(user_info.unstake_shares.unwrap_or(0).saturating_sub(shares));
+        user_info.unstake_usdc_value = Some(user_info.unstake_usdc_value.unwrap_or(0).saturating_sub(usdc_value));
+    }
+}
+
+// Registry slot for a UserShare position, or None if the slot belongs to someone else
+fn registry_user_info_mut<'a>(user_registry: &'a mut UserRegistry, user_share: &UserShare) -> Option<&'a mut UserInfo> {
+    match user_registry.users.get_mut(user_share.registry_index as usize) {
+        Some(Some(user_info)) if user_info.user_pubkey == user_share.user => Some(user_info),
+        _ => None,
+    }
+}
+
+// Mirror a UserShare position into its registry slot (skipped if the slot belongs to someone else)
+fn registry_sync_position(user_registry: &mut Account<UserRegistry>, user_share: &UserShare) {
+    if user_registry.registry_id != user_share.registry_id {
+        return;
+    }
+    if let Some(user_info) = registry_user_info_mut(user_registry, user_share) {
+        user_info.shares = user_share.shares;
+        user_info.cost_basis_per_share = user_share.cost_basis_per_share;
+        user_info.stake_timestamp = user_share.stake_timestamp;
+    }
+}
+
+// A position with no shares and no pending tickets has fully exited: free its registry
+// slot (if still its own) and drop it from the fund's user count
+fn clear_exited_user(user_share: &mut UserShare, user_registry: &mut UserRegistry, fund_pool: &mut FundPool) -> Result<()> {
+    if user_registry.registry_id == user_share.registry_id
+        && registry_user_info_mut(user_registry, user_share).is_some()
+    {
+        user_registry.users[user_share.registry_index as usize] = None;
+        user_registry.user_count = user_registry.user_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+    }
+    fund_pool.total_users = fund_pool.total_users
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user_share.user = Pubkey::default();
+    Ok(())
+}
+
+// Mirror a reduced position in the registry, or clear it once fully exited
+fn registry_sync_or_clear(user_share: &mut UserShare, user_registry: &mut Account<UserRegistry>, fund_pool: &mut FundPool) -> Result<()> {
+    if user_share.shares == 0 && user_share.pending_tickets == 0 {
+        clear_exited_user(user_share, user_registry, fund_pool)
+    } else {
+        registry_sync_position(user_registry, user_share);
+        Ok(())
+    }
+}
+
+// Shares must be held for the configured minimum before any redemption
+fn require_holding_period_met(user_share: &UserShare, config: &FundConfig, current_time: i64) -> Result<()> {
+    let holding_ends = user_share.stake_timestamp
+        .checked_add(config.min_holding_seconds)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(current_time >= holding_ends, ErrorCode::MinimumLockupNotMet);
+    Ok(())
+}
+
+// Rules shared by transfer_shares and the Token-2022 transfer hook. Transfers cannot be used
+// to sidestep the holding period, and the recipient is held to the same limits as stake_usdc.
+fn require_share_transfer_allowed(
+    config: &FundConfig,
+    fund_pool: &Account<FundPool>,
+    sender_share: &UserShare,
+    recipient_share: &UserShare,
+    recipient_allowlist_entry: Option<&AccountInfo>,
+    shares: u64,
+    current_time: i64,
+) -> Result<()> {
+    require_not_paused(config, PAUSE_TRANSFER)?;
+    require_holding_period_met(sender_share, config, current_time)?;
+    if config.allowlist_required {
+        require_allowlisted(recipient_allowlist_entry.ok_or(ErrorCode::NotAllowlisted)?)?;
+    }
+    let recipient_shares_after = recipient_share.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+    require_position_within_limit(config, fund_pool, recipient_shares_after)?;
+    if recipient_share.shares == 0 {
+        require_opening_transfer_above_minimum(config, fund_pool, shares)?;
+    }
+    Ok(())
+}
+
+// Bookkeeping once a matured ticket's USDC has been paid out
+fn settle_unstake_ticket(
+    unstake_ticket: &UnstakeTicket,
+    user_share: &mut UserShare,
+    user_registry: &mut UserRegistry,
+    fund_pool: &mut FundPool,
+    pending_cashout_pool: &mut PendingCashoutPool,
+) -> Result<()> {
+    let final_usdc_amount = unstake_ticket.usdc_value;
+    
+    // Remove this ticket from pending cashout pool
+    pending_cashout_pool.users.retain(|u| !(u.user == unstake_ticket.user && u.nonce == unstake_ticket.nonce));
+    fund_pool.pending_cashout = fund_pool.pending_cashout
+        .checked_sub(final_usdc_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    fund_pool.pending_cashout_funded = fund_pool.pending_cashout_funded.saturating_sub(final_usdc_amount);
+    
+    user_share.pending_tickets = user_share.pending_tickets
+        .checked_sub(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+    
+    if user_share.shares == 0 && user_share.pending_tickets == 0 {
+        // Fully exited (shares already subtracted in initiate_unstake)
+        clear_exited_user(user_share, user_registry, fund_pool)?;
+    } else if let Some(user_info) = registry_user_info_mut(user_registry, user_share) {
+        // Partial exit: remaining shares and tickets stay active
+        registry_remove_pending_unstake(user_info, unstake_ticket.shares, final_usdc_amount, user_share.pending_tickets);
+    }
+    
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn registry_add_user<'a>(
     registry_directory: &mut Account<RegistryDirectory>,
     user_registry: &mut Account<'a, UserRegistry>,
     user: Pubkey,
     shares: u64,
+    cost_basis_per_share: u64,
     stake_timestamp: i64,
+    max_users_per_registry: u64,
     payer: &AccountInfo<'a>,
     system_program: &AccountInfo<'a>,
 ) -> Result<(u64, u64)> {
     // Program-level fallback: Check if registry is full before attempting to add
-    if user_registry.users.len() >= MAX_USERS_PER_REGISTRY as usize {
+    if user_registry.users.len() >= max_users_per_registry as usize {
         msg!("Registry {} is full ({} users). Frontend should try a different registry.", 
              user_registry.registry_id, user_registry.users.len());
         return Err(ErrorCode::RegistryFull.into());
@@ -131,6 +845,7 @@ fn registry_add_user<'a>(
     let user_info = UserInfo {
         user_pubkey: user,
         shares,
+        cost_basis_per_share,
         stake_timestamp,
         unstake_initialized_timestamp: None,
         unstake_shares: None,
@@ -155,7 +870,7 @@ fn registry_add_user<'a>(
         None => {
             // No gaps found, need to extend the array
             let current_data_len = user_registry.to_account_info().data_len();
-            let user_info_size = 32 + 8 + 8 + 9 + 9 + 9; // Pubkey + u64 + i64 + 3 Options
+            let user_info_size = 32 + 8 + 8 + 8 + 9 + 9 + 9; // Pubkey + 2 u64 + i64 + 3 Options
             let additional_space = 1 + user_info_size; // 1 byte for Option discriminator + UserInfo size
             let new_data_len = current_data_len + additional_space;
             
@@ -197,6 +912,105 @@ fn registry_add_user<'a>(
     Ok((user_registry.registry_id, registry_index as u64))
 }
 
+// === PRICE ORACLES ===
+
+// Normalized oracle reading: value of one whole token = price * 10^expo USD
+struct OraclePrice {
+    price: i64,
+    expo: i32,
+    publish_time: i64,
+}
+
+// Source of prices for compute_nav. Implementations validate that `price_account`
+// really is the feed registered in `feed` before returning a price.
+trait PriceOracle {
+    fn read_price(price_account: &AccountInfo, feed: &PriceFeedConfig) -> Result<OraclePrice>;
+}
+
+// Decode a fully verified PriceUpdateV2 account body for `feed_id`
+fn parse_pyth_price_update(data: &[u8], feed_id: &[u8; 32]) -> Result<OraclePrice> {
+    require!(data.len() > PYTH_VERIFICATION_LEVEL_OFFSET, ErrorCode::InvalidPriceAccount);
+    require!(data[..8] == PYTH_PRICE_UPDATE_V2_DISCRIMINATOR, ErrorCode::InvalidPriceAccount);
+    require!(data[PYTH_VERIFICATION_LEVEL_OFFSET] == PYTH_VERIFICATION_FULL, ErrorCode::InvalidPriceAccount);
+
+    // PriceFeedMessage: feed_id, price, conf, exponent, publish_time, ...
+    let message = &data[PYTH_VERIFICATION_LEVEL_OFFSET + 1..];
+    require!(message.len() >= 32 + 8 + 8 + 4 + 8, ErrorCode::InvalidPriceAccount);
+    require!(message[..32] == *feed_id, ErrorCode::InvalidPriceAccount);
+
+    let read_i64 = |offset: usize| i64::from_le_bytes(message[offset..offset + 8].try_into().unwrap());
+    let price = read_i64(32);
+    let expo = i32::from_le_bytes(message[48..52].try_into().unwrap());
+    let publish_time = read_i64(52);
+
+    Ok(OraclePrice { price, expo, publish_time })
+}
+
+// Pyth PriceUpdateV2 accounts posted by the Pyth receiver program (fully verified only)
+struct PythOracle;
+
+impl PriceOracle for PythOracle {
+    fn read_price(price_account: &AccountInfo, feed: &PriceFeedConfig) -> Result<OraclePrice> {
+        require_keys_eq!(*price_account.owner, PYTH_RECEIVER_PROGRAM_ID, ErrorCode::InvalidPriceAccount);
+        parse_pyth_price_update(&price_account.try_borrow_data()?, &feed.feed_id)
+    }
+}
+
+// Program-owned MockPriceFeed PDA set by the admin; only built with the mock-oracle feature
+#[cfg(feature = "mock-oracle")]
+struct MockOracle;
+
+#[cfg(feature = "mock-oracle")]
+impl PriceOracle for MockOracle {
+    fn read_price(price_account: &AccountInfo, feed: &PriceFeedConfig) -> Result<OraclePrice> {
+        let (expected_feed, _) = Pubkey::find_program_address(
+            &[b"mock_price_feed_v2", feed.mint.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(price_account.key(), expected_feed, ErrorCode::InvalidPriceAccount);
+        require_keys_eq!(*price_account.owner, crate::ID, ErrorCode::InvalidPriceAccount);
+        let mock_feed = MockPriceFeed::try_deserialize(&mut &price_account.try_borrow_data()?[..])?;
+
+        Ok(OraclePrice {
+            price: mock_feed.price,
+            expo: mock_feed.expo,
+            publish_time: mock_feed.publish_time,
+        })
+    }
+}
+
+fn read_oracle_price(price_account: &AccountInfo, feed: &PriceFeedConfig, current_time: i64) -> Result<OraclePrice> {
+    match feed.kind {
+        // Stablecoin held at par; no price account is read
+        OracleKind::Par => Ok(OraclePrice { price: 1, expo: 0, publish_time: current_time }),
+        OracleKind::Pyth => PythOracle::read_price(price_account, feed),
+        #[cfg(feature = "mock-oracle")]
+        OracleKind::Mock => MockOracle::read_price(price_account, feed),
+        #[cfg(not(feature = "mock-oracle"))]
+        OracleKind::Mock => Err(ErrorCode::MockOracleDisabled.into()),
+    }
+}
+
+// USDC value of `amount` base units of a token with `token_decimals`
+fn calculate_holding_value(amount: u64, oracle_price: &OraclePrice, token_decimals: u8, usdc_decimals: u8) -> Result<u64> {
+    require!(oracle_price.price > 0, ErrorCode::InvalidOraclePrice);
+
+    // value = amount * price * 10^(expo + usdc_decimals - token_decimals)
+    let scale = oracle_price.expo as i64 + usdc_decimals as i64 - token_decimals as i64;
+    require!(scale.abs() <= 36, ErrorCode::MathOverflow);
+    let factor = 10u128.pow(scale.unsigned_abs() as u32);
+    let raw = (amount as u128)
+        .checked_mul(oracle_price.price as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let value = if scale >= 0 {
+        raw.checked_mul(factor).ok_or(ErrorCode::MathOverflow)?
+    } else {
+        raw / factor
+    };
+
+    u64::try_from(value).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
 #[program]
 pub mod shrub_fund {
     use super::*;
@@ -205,68 +1019,478 @@ pub mod shrub_fund {
 
     // === MAIN PROGRAM FUNCTIONS ===
 
-    pub fn initialize_user_registry(ctx: Context<InitializeUserRegistry>, registry_id: u64) -> Result<()> {
+    pub fn initialize_fund(
+        ctx: Context<InitializeFund>,
+        gardener: Pubkey,
+        trading_wallet: Pubkey,
+        params: FundConfigParams,
+    ) -> Result<()> {
+        validate_fund_config_params(&params)?;
+
+        let config = &mut ctx.accounts.config;
+        config.gardener = gardener;
+        config.trading_wallet = trading_wallet;
+        config.compliance_authority = ctx.accounts.authority.key();
+        config.nav_confirm_authority = ctx.accounts.authority.key();
+        config.usdc_mint = ctx.accounts.usdc_mint.key();
+        apply_fund_config_params(config, &params);
+        config.pending_authority = None;
+        config.pause_flags = 0;
+        config.bump = ctx.bumps.config;
+
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        fund_pool.total_shares = 0;
+        fund_pool.optimized_nav = 0;
+        fund_pool.real_nav = 0;
+        fund_pool.total_users = 0;
+        fund_pool.pending_cashout = 0;
+        fund_pool.pending_cashout_funded = 0;
+        fund_pool.total_cost_basis = 0;
+        fund_pool.authority = ctx.accounts.authority.key();
+        fund_pool.last_commission_timestamp = 0;
+        fund_pool.high_water_nav_per_share = NAV_PRECISION; // Initial NAV = $1.00
+        fund_pool.last_fee_accrual_timestamp = Clock::get()?.unix_timestamp;
+        fund_pool.last_nav_update_timestamp = fund_pool.last_fee_accrual_timestamp;
+        fund_pool.bump = ctx.bumps.fund_pool;
+
+        let nav_history = &mut ctx.accounts.nav_history;
+        nav_history.entries = Vec::new();
+        nav_history.bump = ctx.bumps.nav_history;
+
+        let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
+        pending_cashout_pool.users = Vec::new();
+        pending_cashout_pool.bump = ctx.bumps.pending_cashout_pool;
+
         let registry_directory = &mut ctx.accounts.registry_directory;
-        let user_registry = &mut ctx.accounts.user_registry;
-        
-        require!(registry_id < MAX_REGISTRIES, ErrorCode::InvalidRegistryIndex);
-        require!(registry_directory.total_registries < MAX_REGISTRIES, ErrorCode::RegistryFull);
-        
-        // Initialize registry
-        user_registry.registry_id = registry_id;
-        user_registry.user_count = 0;
-        user_registry.users = Vec::new();
-        
-        // Update directory
-        registry_directory.total_registries = registry_directory.total_registries
-            .checked_add(1)
-            .ok_or(ErrorCode::MathOverflow)?;
-        
-        msg!("User registry {} initialized", registry_id);
+        registry_directory.total_registries = 0;
+        registry_directory.active_users = 0;
+        registry_directory.bump = ctx.bumps.registry_directory;
+
+        msg!("Fund initialized. Authority: {}, Gardener: {}, Trading wallet: {}, USDC mint: {}",
+             fund_pool.authority, config.gardener, config.trading_wallet, config.usdc_mint);
         Ok(())
     }
 
-    pub fn stake_usdc(ctx: Context<StakeUsdc>, usdc_amount: u64) -> Result<()> {
+    pub fn update_fund_config(ctx: Context<UpdateFundConfig>, params: FundConfigParams) -> Result<()> {
+        validate_fund_config_params(&params)?;
+
+        let config = &mut ctx.accounts.config;
+        apply_fund_config_params(config, &params);
+
+        msg!("Fund config updated: holding {}s, notice {}s, commission {} bps every {}s, management fee {} bps/year, exit fee {} bps, NAV window {}s, gate {} bps per {}s, TVL cap {}, min deposit {}, max position {}, allowlist {}, NAV quorum {} within {} bps, NAV every {}s moving <= {} bps and stale after {}s, oracle max age {}s, registries {} x {} users",
+             config.min_holding_seconds, config.unstake_lockup_seconds,
+             config.commission_bps, config.commission_period_seconds,
+             config.management_fee_bps, config.exit_fee_bps, config.nav_window_seconds,
+             config.redemption_gate_bps, config.redemption_window_seconds,
+             config.max_tvl_usdc, config.min_deposit_usdc, config.max_position_usdc, config.allowlist_required,
+             config.nav_quorum, config.nav_tolerance_bps,
+             config.min_nav_update_interval_seconds, config.max_nav_change_bps, config.max_nav_staleness_seconds,
+             config.oracle_max_age_seconds,
+             config.max_registries, config.max_users_per_registry);
+        Ok(())
+    }
+
+    // Program-owned custody for staked USDC; fund_pool PDA is the token authority
+    pub fn initialize_trading_vault(ctx: Context<InitializeTradingVault>) -> Result<()> {
+        msg!("Trading vault {} initialized for mint {}",
+             ctx.accounts.trading_vault.key(), ctx.accounts.config.usdc_mint);
+        Ok(())
+    }
+
+    // Program-controlled share token; fund_pool PDA is the mint authority and supply tracks total_shares
+    pub fn initialize_share_mint(ctx: Context<InitializeShareMint>) -> Result<()> {
+        // Supply starts at zero, so shares must not exist yet
+        require!(ctx.accounts.fund_pool.total_shares == 0, ErrorCode::SharesAlreadyIssued);
+
+        msg!("Share mint {} initialized ({} decimals)", ctx.accounts.share_mint.key(), SHARE_DECIMALS);
+        Ok(())
+    }
+
+    // Writes the ExtraAccountMetaList Token-2022 uses to resolve transfer_hook accounts
+    pub fn initialize_share_hook(ctx: Context<InitializeShareHook>) -> Result<()> {
+        let account_metas = share_hook_extra_account_metas()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &account_metas,
+        )?;
+
+        msg!("Share transfer hook initialized with {} extra accounts", account_metas.len());
+        Ok(())
+    }
+
+    // Token-2022 transfer hook for the share mint: moves the position between UserShare
+    // records (and their registry mirrors) so holdings always match token balances
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+        check_is_transferring(&ctx.accounts.source_token.to_account_info())?;
+
+        // Self-transfers and zero transfers leave positions unchanged
+        if amount == 0 || ctx.accounts.sender_share.key() == ctx.accounts.receiver_share.key() {
+            return Ok(());
+        }
+
+        let sender_share = &mut ctx.accounts.sender_share;
+        let receiver_share = &mut ctx.accounts.receiver_share;
         let fund_pool = &mut ctx.accounts.fund_pool;
-        let user_share = &mut ctx.accounts.user_share;
         let clock = Clock::get()?;
 
-        // Calculate shares based on REAL NAV to prevent discount purchases
-        let shares = calculate_shares_for_usdc_real_nav(fund_pool, usdc_amount)?;
-        require!(shares > 0, ErrorCode::InsufficientAmount);
+        // Only active shares can move; shares in a pending unstake were burned at initiation
+        require!(amount <= sender_share.shares, ErrorCode::SharesLocked);
+        require!(
+            receiver_share.user == ctx.accounts.destination_token.owner,
+            ErrorCode::RecipientNotRegistered
+        );
+        require_share_transfer_allowed(
+            &ctx.accounts.config,
+            fund_pool,
+            sender_share,
+            receiver_share,
+            Some(&ctx.accounts.receiver_allowlist_entry),
+            amount,
+            clock.unix_timestamp,
+        )?;
+
+        // Receiver keeps its own holding period
+        move_shares_with_cost_basis(sender_share, receiver_share, amount)?;
+
+        // Both registries are serialized on exit in field order; when they are the same
+        // account, apply both updates to the receiver copy so neither is lost
+        let sender_registry = if ctx.accounts.sender_registry.key() == ctx.accounts.receiver_registry.key() {
+            &mut ctx.accounts.receiver_registry
+        } else {
+            &mut ctx.accounts.sender_registry
+        };
+        registry_sync_or_clear(sender_share, sender_registry, fund_pool)?;
+        registry_sync_position(&mut ctx.accounts.receiver_registry, receiver_share);
+
+        msg!("Share transfer: {} shares {} -> {}",
+             amount, ctx.accounts.source_token.owner, receiver_share.user);
+        Ok(())
+    }
+
+    // Program-owned vault that complete_unstake pays from; pending_cashout_pool PDA is the token authority
+    pub fn initialize_pending_cashout_vault(ctx: Context<InitializePendingCashoutVault>) -> Result<()> {
+        msg!("Pending cashout vault {} initialized for mint {}",
+             ctx.accounts.pending_cashout_usdc_account.key(), ctx.accounts.config.usdc_mint);
+        Ok(())
+    }
+
+    // Gardener moves USDC from the trading vault into the pending cashout vault so matured unstakes are payable.
+    // Defaults to the uncovered part of fund_pool.pending_cashout when no amount is given.
+    pub fn fund_pending_cashouts(ctx: Context<FundPendingCashouts>, amount: Option<u64>) -> Result<()> {
+        let fund_pool = &mut ctx.accounts.fund_pool;
+
+        let shortfall = fund_pool.pending_cashout.saturating_sub(fund_pool.pending_cashout_funded);
+        // Never move more than the outstanding shortfall out of the trading vault
+        let amount = amount.unwrap_or(shortfall).min(shortfall);
+        require!(amount > 0, ErrorCode::InsufficientAmount);
+        require!(ctx.accounts.trading_vault.amount >= amount, ErrorCode::InsufficientFunds);
 
-        // Transfer USDC from user to trading wallet
+        let fund_pool_seeds = &[
+            b"optimized_fund_pool_v2".as_ref(),
+            &[fund_pool.bump],
+        ];
         token_interface::transfer(
-            CpiContext::new(
+            CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 InterfaceTransfer {
-                    from: ctx.accounts.user_usdc_account.to_account_info(),
-                    to: ctx.accounts.trading_usdc_account.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
+                    from: ctx.accounts.trading_vault.to_account_info(),
+                    to: ctx.accounts.pending_cashout_usdc_account.to_account_info(),
+                    authority: fund_pool.to_account_info(),
                 },
+                &[fund_pool_seeds],
             ),
-            usdc_amount,
+            amount,
         )?;
 
-        // Track if this is user's first stake
-        let is_first_stake = user_share.user == Pubkey::default();
+        fund_pool.pending_cashout_funded = fund_pool.pending_cashout_funded
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
 
-        // Initialize user share if first time
-        if is_first_stake {
-            user_share.user = ctx.accounts.user.key();
-            user_share.shares = 0;
-            user_share.stake_timestamp = clock.unix_timestamp;
-            fund_pool.total_users = fund_pool.total_users
-                .checked_add(1)
+        msg!("Funded {} USDC for pending cashouts. Covered: {} / {} USDC",
+             amount, fund_pool.pending_cashout_funded, fund_pool.pending_cashout);
+        Ok(())
+    }
+
+    // Program-owned vault that instant_unstake pays from; fund_pool PDA is the token authority
+    pub fn initialize_liquidity_buffer_vault(ctx: Context<InitializeLiquidityBufferVault>) -> Result<()> {
+        msg!("Liquidity buffer vault {} initialized for mint {}",
+             ctx.accounts.liquidity_buffer_vault.key(), ctx.accounts.config.usdc_mint);
+        Ok(())
+    }
+
+    // Gardener tops up the liquidity buffer from the trading vault. Buffer USDC is still
+    // fund property and counted in real_nav until paid out.
+    pub fn fund_liquidity_buffer(ctx: Context<FundLiquidityBuffer>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InsufficientAmount);
+        require!(ctx.accounts.trading_vault.amount >= amount, ErrorCode::InsufficientFunds);
+
+        let fund_pool_seeds = &[
+            b"optimized_fund_pool_v2".as_ref(),
+            &[ctx.accounts.fund_pool.bump],
+        ];
+        token_interface::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                InterfaceTransfer {
+                    from: ctx.accounts.trading_vault.to_account_info(),
+                    to: ctx.accounts.liquidity_buffer_vault.to_account_info(),
+                    authority: ctx.accounts.fund_pool.to_account_info(),
+                },
+                &[fund_pool_seeds],
+            ),
+            amount,
+        )?;
+
+        msg!("Moved {} USDC from trading vault to liquidity buffer. Buffer balance: {} USDC",
+             amount, ctx.accounts.liquidity_buffer_vault.amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?);
+        Ok(())
+    }
+
+    // Gardener moves vault USDC out to the trading wallet to deploy into the strategy
+    pub fn withdraw_to_trading_wallet(ctx: Context<WithdrawToTradingWallet>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InsufficientAmount);
+        require!(ctx.accounts.trading_vault.amount >= amount, ErrorCode::InsufficientFunds);
+
+        let fund_pool_seeds = &[
+            b"optimized_fund_pool_v2".as_ref(),
+            &[ctx.accounts.fund_pool.bump],
+        ];
+        token_interface::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                InterfaceTransfer {
+                    from: ctx.accounts.trading_vault.to_account_info(),
+                    to: ctx.accounts.trading_usdc_account.to_account_info(),
+                    authority: ctx.accounts.fund_pool.to_account_info(),
+                },
+                &[fund_pool_seeds],
+            ),
+            amount,
+        )?;
+
+        msg!("Moved {} USDC from trading vault to trading wallet", amount);
+        Ok(())
+    }
+
+    // Trading wallet returns USDC to the vault (profits, liquidity for fees and cashouts)
+    pub fn deposit_to_trading_vault(ctx: Context<DepositToTradingVault>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InsufficientAmount);
+
+        token_interface::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                InterfaceTransfer {
+                    from: ctx.accounts.trading_usdc_account.to_account_info(),
+                    to: ctx.accounts.trading_vault.to_account_info(),
+                    authority: ctx.accounts.trading_wallet.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        msg!("Moved {} USDC from trading wallet to trading vault", amount);
+        Ok(())
+    }
+
+    // Circuit breaker: each bit halts one operation (see PAUSE_* constants)
+    pub fn set_pause_flags(ctx: Context<SetPauseFlags>, pause_flags: u8) -> Result<()> {
+        require!(pause_flags & !PAUSE_ALL == 0, ErrorCode::InvalidConfig);
+
+        let config = &mut ctx.accounts.config;
+        let old_flags = config.pause_flags;
+        config.pause_flags = pause_flags;
+
+        msg!("Pause flags updated: {:#08b} -> {:#08b}", old_flags, pause_flags);
+        Ok(())
+    }
+
+    // Step 1 of authority rotation: current fund authority nominates the new key
+    pub fn propose_authority(
+        ctx: Context<ProposeAuthority>,
+        role: AuthorityRole,
+        new_authority: Pubkey,
+        delay_seconds: i64,
+    ) -> Result<()> {
+        require!(delay_seconds >= 0, ErrorCode::InvalidConfig);
+        let config = &mut ctx.accounts.config;
+        let clock = Clock::get()?;
+
+        let effective_timestamp = clock.unix_timestamp
+            .checked_add(delay_seconds)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        config.pending_authority = Some(PendingAuthority {
+            role,
+            new_authority,
+            effective_timestamp,
+        });
+
+        msg!("Authority rotation proposed: {:?} -> {}, effective at {}", role, new_authority, effective_timestamp);
+        Ok(())
+    }
+
+    // Step 2 of authority rotation: the nominated key signs to take over once the timelock expires
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let clock = Clock::get()?;
+
+        let pending = config.pending_authority.clone().ok_or(ErrorCode::NoPendingAuthority)?;
+        require!(
+            pending.new_authority == ctx.accounts.new_authority.key(),
+            ErrorCode::InvalidPendingAuthority
+        );
+        require!(
+            clock.unix_timestamp >= pending.effective_timestamp,
+            ErrorCode::AuthorityTimelockActive
+        );
+
+        let old_authority = match pending.role {
+            AuthorityRole::Admin => std::mem::replace(&mut fund_pool.authority, pending.new_authority),
+            AuthorityRole::Gardener => std::mem::replace(&mut config.gardener, pending.new_authority),
+            AuthorityRole::TradingWallet => std::mem::replace(&mut config.trading_wallet, pending.new_authority),
+            AuthorityRole::Compliance => std::mem::replace(&mut config.compliance_authority, pending.new_authority),
+            AuthorityRole::NavConfirmer => std::mem::replace(&mut config.nav_confirm_authority, pending.new_authority),
+        };
+        config.pending_authority = None;
+
+        msg!("Authority rotated: {:?} {} -> {}", pending.role, old_authority, pending.new_authority);
+        Ok(())
+    }
+
+    // Compliance authority attests that a wallet passed KYC / accreditation and may stake
+    pub fn approve_allowlist(ctx: Context<ApproveAllowlist>, user: Pubkey) -> Result<()> {
+        let allowlist_entry = &mut ctx.accounts.allowlist_entry;
+        let clock = Clock::get()?;
+
+        allowlist_entry.user = user;
+        allowlist_entry.approved_by = ctx.accounts.compliance_authority.key();
+        allowlist_entry.approved_timestamp = clock.unix_timestamp;
+        allowlist_entry.revoked = false;
+        allowlist_entry.revoked_timestamp = 0;
+        allowlist_entry.bump = ctx.bumps.allowlist_entry;
+
+        msg!("Allowlist approved: {} by {}", user, allowlist_entry.approved_by);
+        Ok(())
+    }
+
+    // Revocation blocks further deposits; existing positions can still be unstaked
+    pub fn revoke_allowlist(ctx: Context<RevokeAllowlist>, user: Pubkey) -> Result<()> {
+        let allowlist_entry = &mut ctx.accounts.allowlist_entry;
+        let clock = Clock::get()?;
+
+        allowlist_entry.revoked = true;
+        allowlist_entry.revoked_timestamp = clock.unix_timestamp;
+
+        msg!("Allowlist revoked: {} by {}", user, ctx.accounts.compliance_authority.key());
+        Ok(())
+    }
+
+    pub fn initialize_user_registry(ctx: Context<InitializeUserRegistry>, registry_id: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let registry_directory = &mut ctx.accounts.registry_directory;
+        let user_registry = &mut ctx.accounts.user_registry;
+        
+        require!(registry_id < config.max_registries, ErrorCode::InvalidRegistryIndex);
+        require!(registry_directory.total_registries < config.max_registries, ErrorCode::RegistryFull);
+        
+        // Initialize registry
+        user_registry.registry_id = registry_id;
+        user_registry.user_count = 0;
+        user_registry.users = Vec::new();
+        
+        // Update directory
+        registry_directory.total_registries = registry_directory.total_registries
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        
+        msg!("User registry {} initialized", registry_id);
+        Ok(())
+    }
+
+    pub fn stake_usdc(ctx: Context<StakeUsdc>, usdc_amount: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_STAKE)?;
+
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let user_share = &mut ctx.accounts.user_share;
+        let clock = Clock::get()?;
+
+        // Deposit limits: dust floor, strategy capacity, per-wallet cap
+        let config = &ctx.accounts.config;
+        require_fresh_nav(fund_pool, config, clock.unix_timestamp)?;
+        if config.allowlist_required {
+            let allowlist_entry = ctx.accounts.allowlist_entry.as_ref().ok_or(ErrorCode::NotAllowlisted)?;
+            require!(!allowlist_entry.revoked, ErrorCode::AllowlistRevoked);
+        }
+        require!(usdc_amount >= config.min_deposit_usdc, ErrorCode::DepositBelowMinimum);
+        if config.max_tvl_usdc > 0 {
+            let tvl_after = fund_pool.real_nav
+                .checked_add(usdc_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+            require!(tvl_after <= config.max_tvl_usdc, ErrorCode::DepositCapExceeded);
+        }
+
+        // Calculate shares based on REAL NAV to prevent discount purchases
+        let shares = calculate_shares_for_usdc_real_nav(fund_pool, usdc_amount)?;
+        require!(shares > 0, ErrorCode::InsufficientAmount);
+        let shares_after = user_share.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        require_position_within_limit(config, fund_pool, shares_after)?;
+
+        // Transfer USDC from user to the program-owned trading vault
+        token_interface::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                InterfaceTransfer {
+                    from: ctx.accounts.user_usdc_account.to_account_info(),
+                    to: ctx.accounts.trading_vault.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            usdc_amount,
+        )?;
+
+        // Track if this is user's first stake
+        let is_first_stake = user_share.user == Pubkey::default();
+
+        // Initialize user share if first time
+        if is_first_stake {
+            user_share.user = ctx.accounts.user.key();
+            user_share.shares = 0;
+            user_share.cost_basis_per_share = 0;
+            fund_pool.total_users = fund_pool.total_users
+                .checked_add(1)
                 .ok_or(ErrorCode::MathOverflow)?;
         }
 
+        // Every stake restarts the minimum holding period, so a top-up cannot exit straight away
+        user_share.stake_timestamp = clock.unix_timestamp;
+
+        // Blend this purchase into the user's weighted-average cost basis
+        user_share.cost_basis_per_share = calculate_weighted_cost_basis(
+            user_share.shares,
+            user_share.cost_basis_per_share,
+            shares,
+            usdc_amount,
+        )?;
+
         // Update user and fund totals
         user_share.shares = user_share.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
         fund_pool.total_shares = fund_pool.total_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.total_cost_basis = fund_pool.total_cost_basis.checked_add(usdc_amount).ok_or(ErrorCode::MathOverflow)?;
         
-        // Add USDC amount to real_nav since USDC is being transferred in
+        // Add USDC amount to both NAVs since USDC is being transferred in
         fund_pool.real_nav = fund_pool.real_nav.checked_add(usdc_amount).ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.optimized_nav = fund_pool.optimized_nav.checked_add(usdc_amount).ok_or(ErrorCode::MathOverflow)?;
+
+        // Mint matching share tokens to the user
+        mint_share_tokens(
+            &ctx.accounts.share_token_program,
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_token_account,
+            fund_pool,
+            shares,
+        )?;
 
         // Registry Integration: Add user to central registry
         if is_first_stake {
@@ -277,7 +1501,9 @@ pub mod shrub_fund {
                         user_registry,
                         ctx.accounts.user.key(),
                         user_share.shares,
+                        user_share.cost_basis_per_share,
                         user_share.stake_timestamp,
+                        ctx.accounts.config.max_users_per_registry,
                         &ctx.accounts.user.to_account_info(),
                         &ctx.accounts.system_program.to_account_info(),
                     )?;
@@ -285,6 +1511,10 @@ pub mod shrub_fund {
                     user_share.registry_index = registry_index;
                 } 
             }
+        } else if let Some(user_registry) = &mut ctx.accounts.user_registry {
+            // Returning user: keep registry mirror in sync with UserShare
+            require!(user_registry.registry_id == user_share.registry_id, ErrorCode::InvalidRegistryIndex);
+            registry_sync_position(user_registry, user_share);
         }
 
         let current_optimized_nav = calculate_optimized_nav_per_share(fund_pool)?;
@@ -296,33 +1526,188 @@ pub mod shrub_fund {
         Ok(())
     }
 
-    pub fn initiate_unstake(ctx: Context<InitiateUnstake>) -> Result<()> {
+    // Move active shares to another wallet without unstaking (own wallets, estate transfers).
+    // Share tokens are burned from the sender and minted to the recipient, so the Token-2022
+    // hook is not involved and both UserShare records are updated here directly.
+    pub fn transfer_shares(ctx: Context<TransferShares>, shares: u64) -> Result<()> {
+        require!(ctx.accounts.recipient.key() != ctx.accounts.sender.key(), ErrorCode::InvalidRecipient);
+
+        let sender_share = &mut ctx.accounts.sender_share;
+        let recipient_share = &mut ctx.accounts.recipient_share;
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let clock = Clock::get()?;
+
+        require!(sender_share.shares > 0, ErrorCode::NoShares);
+        require!(shares > 0 && shares <= sender_share.shares, ErrorCode::InvalidShareAmount);
+        let recipient_allowlist_entry = ctx.accounts.recipient_allowlist_entry.as_ref().map(|entry| entry.to_account_info());
+        require_share_transfer_allowed(
+            &ctx.accounts.config,
+            fund_pool,
+            sender_share,
+            recipient_share,
+            recipient_allowlist_entry.as_ref(),
+            shares,
+            clock.unix_timestamp,
+        )?;
+
+        // Move share tokens
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                InterfaceBurn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.sender_share_token_account.to_account_info(),
+                    authority: ctx.accounts.sender.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+        mint_share_tokens(
+            &ctx.accounts.share_token_program,
+            &ctx.accounts.share_mint,
+            &ctx.accounts.recipient_share_token_account,
+            fund_pool,
+            shares,
+        )?;
+
+        // Initialize recipient share if first time
+        let is_new_recipient = recipient_share.user == Pubkey::default();
+        if is_new_recipient {
+            recipient_share.user = ctx.accounts.recipient.key();
+            recipient_share.shares = 0;
+            recipient_share.cost_basis_per_share = 0;
+            // A new recipient inherits the sender's already-elapsed holding period
+            recipient_share.stake_timestamp = sender_share.stake_timestamp;
+            fund_pool.total_users = fund_pool.total_users
+                .checked_add(1)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        // An existing recipient keeps its own holding period
+        move_shares_with_cost_basis(sender_share, recipient_share, shares)?;
+
+        // Registry Integration: add new recipient through the same path as stake_usdc
+        if is_new_recipient {
+            if let Some(registry_directory) = &mut ctx.accounts.registry_directory {
+                if let Some(user_registry) = &mut ctx.accounts.user_registry {
+                    let (registry_id, registry_index) = registry_add_user(
+                        registry_directory,
+                        user_registry,
+                        ctx.accounts.recipient.key(),
+                        recipient_share.shares,
+                        recipient_share.cost_basis_per_share,
+                        recipient_share.stake_timestamp,
+                        ctx.accounts.config.max_users_per_registry,
+                        &ctx.accounts.sender.to_account_info(),
+                        &ctx.accounts.system_program.to_account_info(),
+                    )?;
+                    recipient_share.registry_id = registry_id;
+                    recipient_share.registry_index = registry_index;
+                }
+            }
+        } else if let Some(user_registry) = &mut ctx.accounts.user_registry {
+            require!(user_registry.registry_id == recipient_share.registry_id, ErrorCode::InvalidRegistryIndex);
+            registry_sync_position(user_registry, recipient_share);
+        }
+
+        // Both registries are serialized on exit in field order; when they are the same
+        // account, apply the sender update to the recipient copy so neither is lost
+        let sender_registry = match &mut ctx.accounts.user_registry {
+            Some(user_registry) if user_registry.key() == ctx.accounts.sender_registry.key() => user_registry,
+            _ => &mut ctx.accounts.sender_registry,
+        };
+        registry_sync_or_clear(sender_share, sender_registry, fund_pool)?;
+
+        msg!("Transferred {} shares from {} to {}", shares, ctx.accounts.sender.key(), ctx.accounts.recipient.key());
+        Ok(())
+    }
+
+    pub fn initiate_unstake(ctx: Context<InitiateUnstake>, shares: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_INITIATE_UNSTAKE)?;
+
         let user_share = &mut ctx.accounts.user_share;
         let fund_pool = &mut ctx.accounts.fund_pool;
         let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
         let user_registry = &mut ctx.accounts.user_registry;
+        let unstake_ticket = &mut ctx.accounts.unstake_ticket;
         let clock = Clock::get()?;
 
         require!(user_share.shares > 0, ErrorCode::NoShares);
-        require!(!user_share.unstake_initiated, ErrorCode::UnstakeAlreadyPending);
+        require!(shares > 0 && shares <= user_share.shares, ErrorCode::InvalidShareAmount);
 
-        // Calculate and LOCK USDC value at current optimized NAV
-        let locked_usdc_value = calculate_usdc_for_shares(fund_pool, user_share.shares)?;
+        require_holding_period_met(user_share, &ctx.accounts.config, clock.unix_timestamp)?;
+        require_fresh_nav(fund_pool, &ctx.accounts.config, clock.unix_timestamp)?;
 
-        // Update user registry with unstake info
-        if let Some(user_info) = &mut user_registry.users[user_share.registry_index as usize] {
-            user_info.unstake_initialized_timestamp = Some(clock.unix_timestamp);
-            user_info.unstake_shares = Some(user_share.shares);
-            user_info.unstake_usdc_value = Some(locked_usdc_value);
+        // Pay the account passed in, or fall back to the destination from an earlier request
+        let destination = match &ctx.accounts.destination_usdc_account {
+            Some(destination_usdc_account) => destination_usdc_account.key(),
+            None => user_share.withdrawal_destination,
+        };
+        require!(destination != Pubkey::default(), ErrorCode::InvalidDestination);
+
+        // Calculate and LOCK USDC value at current optimized NAV for just this portion
+        let locked_usdc_value = calculate_usdc_for_shares(fund_pool, shares)?;
+        require!(locked_usdc_value > 0, ErrorCode::InsufficientAmount);
+        let released_cost_basis = calculate_cost_of_shares(shares, user_share.cost_basis_per_share)?;
+
+        // Requests over this window's redemption gate are deferred to a later window
+        let (release_timestamp, release_window_start) = apply_redemption_gate(fund_pool, &ctx.accounts.config, locked_usdc_value, clock.unix_timestamp)?;
+        if release_timestamp > clock.unix_timestamp {
+            msg!("Redemption gate reached: request deferred to window starting {}", release_timestamp);
         }
+        let maturity_timestamp = release_timestamp
+            .checked_add(ctx.accounts.config.unstake_lockup_seconds)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Burn the share tokens being redeemed
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                InterfaceBurn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        // Remaining shares stay active and keep earning
+        user_share.shares = user_share.shares
+            .checked_sub(shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Issue a ticket for this request
+        let nonce = user_share.next_ticket_nonce;
+        user_share.next_ticket_nonce = nonce.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        user_share.pending_tickets = user_share.pending_tickets
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        unstake_ticket.user = ctx.accounts.user.key();
+        unstake_ticket.nonce = nonce;
+        unstake_ticket.shares = shares;
+        unstake_ticket.usdc_value = locked_usdc_value;
+        unstake_ticket.initiated_timestamp = clock.unix_timestamp;
+        unstake_ticket.maturity_timestamp = maturity_timestamp;
+        unstake_ticket.destination = destination;
+        unstake_ticket.release_window_start = release_window_start;
+        unstake_ticket.bump = ctx.bumps.unstake_ticket;
 
-        // Mark user as having unstake initiated
-        user_share.unstake_initiated = true;
+        // Remember the payout destination as the default for future requests
+        user_share.withdrawal_destination = destination;
+
+        // Update user registry with unstake info
+        if let Some(user_info) = registry_user_info_mut(user_registry, user_share) {
+            user_info.shares = user_share.shares;
+            registry_add_pending_unstake(user_info, shares, locked_usdc_value, clock.unix_timestamp)?;
+        }
 
         // Add to pending cashout pool - expand if needed
         let pending_user = PendingUsers {
             user: ctx.accounts.user.key(),
             pending_usdc_cashout: locked_usdc_value,
+            nonce,
         };
         
         // Check if we need to expand the PDA (gap filling happens automatically via retain())
@@ -341,130 +1726,691 @@ pub mod shrub_fund {
 
         // Immediately subtract both shares and value from fund pool
         fund_pool.total_shares = fund_pool.total_shares
-            .checked_sub(user_share.shares)
+            .checked_sub(shares)
             .ok_or(ErrorCode::MathOverflow)?;
         fund_pool.real_nav = fund_pool.real_nav
             .checked_sub(locked_usdc_value)
             .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.optimized_nav = fund_pool.optimized_nav
+            .checked_sub(locked_usdc_value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.total_cost_basis = fund_pool.total_cost_basis.saturating_sub(released_cost_basis);
 
-        msg!("Unstake initiated: {} shares, completion in 7 days. Locked value: {} USDC", 
-             user_share.shares, locked_usdc_value);
+        msg!("Unstake ticket {} initiated: {} shares ({} remaining), matures at {}. Locked value: {} USDC, payout to {}", 
+             nonce, shares, user_share.shares, maturity_timestamp, locked_usdc_value, unstake_ticket.destination);
         Ok(())
     }
 
-    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+    // Skip the notice period: redeem shares at current optimized NAV straight from the liquidity
+    // buffer. The exit fee stays in the fund, raising NAV per share for remaining holders.
+    pub fn instant_unstake(ctx: Context<InstantUnstake>, shares: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_INSTANT_UNSTAKE)?;
+
         let user_share = &mut ctx.accounts.user_share;
         let fund_pool = &mut ctx.accounts.fund_pool;
-        let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
         let user_registry = &mut ctx.accounts.user_registry;
         let clock = Clock::get()?;
 
-        require!(user_share.unstake_initiated, ErrorCode::NoUnstakePending);
-        
-        // Check 7-day waiting period
-        let user_info = &user_registry.users[user_share.registry_index as usize]
-            .as_ref()
-            .ok_or(ErrorCode::UserNotInRegistry)?;
-        
-        let seven_days = 7 * 24 * 60 * 60;
-        require!(
-            clock.unix_timestamp >= user_info.unstake_initialized_timestamp.unwrap() + seven_days,
-            ErrorCode::UnstakeNotReady
-        );
+        require!(user_share.shares > 0, ErrorCode::NoShares);
+        require!(shares > 0 && shares <= user_share.shares, ErrorCode::InvalidShareAmount);
 
-        // Use the locked USDC value from initiation
-        let final_usdc_amount = user_info.unstake_usdc_value.unwrap();
-        require!(final_usdc_amount > 0, ErrorCode::InsufficientFundValue);
+        // Instant exits honour the same minimum holding period as initiate_unstake
+        require_holding_period_met(user_share, &ctx.accounts.config, clock.unix_timestamp)?;
+        require_fresh_nav(fund_pool, &ctx.accounts.config, clock.unix_timestamp)?;
 
-        // Transfer USDC from pending cashout pool PDA to user
-        let pending_cashout_seeds = &[
-            b"pending_cashout_pool_v2".as_ref(),
-            &[ctx.bumps.pending_cashout_pool],
+        let gross_usdc_value = calculate_usdc_for_shares(fund_pool, shares)?;
+        let exit_fee = (gross_usdc_value as u128)
+            .checked_mul(ctx.accounts.config.exit_fee_bps as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(BPS_DENOMINATOR as u128)
+            .ok_or(ErrorCode::MathOverflow)? as u64;
+        let payout = gross_usdc_value
+            .checked_sub(exit_fee)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(payout > 0, ErrorCode::InsufficientAmount);
+        require!(ctx.accounts.liquidity_buffer_vault.amount >= payout, ErrorCode::InsufficientLiquidityBuffer);
+        let released_cost_basis = calculate_cost_of_shares(shares, user_share.cost_basis_per_share)?;
+
+        // Burn the share tokens being redeemed
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.share_token_program.to_account_info(),
+                InterfaceBurn {
+                    mint: ctx.accounts.share_mint.to_account_info(),
+                    from: ctx.accounts.user_share_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                },
+            ),
+            shares,
+        )?;
+
+        // Pay out of the buffer, signed by the fund_pool PDA
+        let fund_pool_seeds = &[
+            b"optimized_fund_pool_v2".as_ref(),
+            &[fund_pool.bump],
         ];
         token_interface::transfer(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 InterfaceTransfer {
-                    from: ctx.accounts.pending_cashout_usdc_account.to_account_info(),
-                    to: ctx.accounts.user_usdc_account.to_account_info(),
-                    authority: pending_cashout_pool.to_account_info(),
+                    from: ctx.accounts.liquidity_buffer_vault.to_account_info(),
+                    to: ctx.accounts.destination_usdc_account.to_account_info(),
+                    authority: fund_pool.to_account_info(),
                 },
-                &[pending_cashout_seeds],
+                &[fund_pool_seeds],
             ),
-            final_usdc_amount,
+            payout,
         )?;
 
-        // Clear user from registry
-        user_registry.users[user_share.registry_index as usize] = None;
-        user_registry.user_count = user_registry.user_count
-            .checked_sub(1)
+        user_share.shares = user_share.shares
+            .checked_sub(shares)
             .ok_or(ErrorCode::MathOverflow)?;
 
-        // Remove from pending cashout pool
-        pending_cashout_pool.users.retain(|u| u.user != ctx.accounts.user.key());
-        fund_pool.pending_cashout = fund_pool.pending_cashout
-            .checked_sub(final_usdc_amount)
+        // Only the payout leaves the fund; the exit fee stays in both NAVs
+        fund_pool.total_shares = fund_pool.total_shares
+            .checked_sub(shares)
             .ok_or(ErrorCode::MathOverflow)?;
-
-        // Update fund totals (shares already subtracted in initiate_unstake)
-        fund_pool.total_users = fund_pool.total_users
-            .checked_sub(1)
+        fund_pool.real_nav = fund_pool.real_nav
+            .checked_sub(payout)
             .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.optimized_nav = fund_pool.optimized_nav
+            .checked_sub(payout)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.total_cost_basis = fund_pool.total_cost_basis.saturating_sub(released_cost_basis);
 
-        // Clear user share
-        user_share.user = Pubkey::default();
-        user_share.shares = 0;
-        user_share.unstake_initiated = false;
+        registry_sync_or_clear(user_share, user_registry, fund_pool)?;
 
-        msg!("Unstake completed: {} USDC transferred to user", final_usdc_amount);
+        msg!("Instant unstake: {} shares for {} USDC (exit fee {} USDC retained). Paid to {}",
+             shares, payout, exit_fee, ctx.accounts.destination_usdc_account.key());
         Ok(())
     }
 
-    pub fn update_optimized_nav(ctx: Context<UpdateOptimizedNav>, new_portfolio_value: u64) -> Result<()> {
-        // Only gardener (Lambda) can update NAV
-        require!(
-            ctx.accounts.gardener.key() == GARDENER_PUBKEY,
-            ErrorCode::UnauthorizedGardener
-        );
+    // Withdraw a pending unstake ticket before maturity. The locked USDC value is re-priced into
+    // shares at the current real NAV, capped at the shares originally unstaked, so a user
+    // can never gain shares by cancelling; any shortfall stays with remaining holders.
+    pub fn cancel_unstake(ctx: Context<CancelUnstake>, nonce: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_INITIATE_UNSTAKE)?;
 
+        let user_share = &mut ctx.accounts.user_share;
         let fund_pool = &mut ctx.accounts.fund_pool;
-        let nav_history = &mut ctx.accounts.nav_history;
-        let pending_cashout_pool = &ctx.accounts.pending_cashout_pool;
+        let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
+        let user_registry = &mut ctx.accounts.user_registry;
+        let unstake_ticket = &ctx.accounts.unstake_ticket;
         let clock = Clock::get()?;
 
-        let old_optimized_nav = fund_pool.optimized_nav;
-        
-        // Always update real NAV immediately
-        fund_pool.real_nav = new_portfolio_value;
-        
-        // Update NAV history and get all real_nav values for averaging
-        let _real_navs = update_nav_history(nav_history, new_portfolio_value, clock.unix_timestamp)?;
-        let calculated_optimized_nav = calculate_average_nav(&nav_history.entries)?;
-        
-        // Apply one-directional averaged NAV system logic
-        let final_optimized_nav = if new_portfolio_value < old_optimized_nav {
-            // NAV decrease: apply immediately
-            new_portfolio_value
-        } else {
-            // NAV increase: use averaged NAV
-            calculated_optimized_nav
-        };
-        
-        fund_pool.optimized_nav = final_optimized_nav;
+        require!(
+            clock.unix_timestamp < unstake_ticket.maturity_timestamp,
+            ErrorCode::UnstakeMatured
+        );
 
-        // Calculate total pending cashout for alert
-        let total_pending: u64 = pending_cashout_pool.users.iter()
-            .map(|u| u.pending_usdc_cashout)
-            .sum();
-        fund_pool.pending_cashout = total_pending;
+        let locked_usdc_value = unstake_ticket.usdc_value;
+        let original_shares = unstake_ticket.shares;
 
-        if total_pending > 0 {
-            msg!("ALERT: {} USDC needed for pending cashouts", total_pending);
-        }
+        // Re-price at current real NAV (before the locked value re-enters the pool)
+        let restored_shares = calculate_shares_for_usdc_real_nav(fund_pool, locked_usdc_value)?
+            .min(original_shares);
+        let restored_cost_basis = calculate_cost_of_shares(restored_shares, user_share.cost_basis_per_share)?;
 
-        msg!("NAV updated: Optimized {} USDC, Real {} USDC", 
-             final_optimized_nav, new_portfolio_value);
-        Ok(())
+        // Return value and shares to the fund
+        fund_pool.real_nav = fund_pool.real_nav
+            .checked_add(locked_usdc_value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.optimized_nav = fund_pool.optimized_nav
+            .checked_add(locked_usdc_value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.total_shares = fund_pool.total_shares
+            .checked_add(restored_shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.total_cost_basis = fund_pool.total_cost_basis
+            .checked_add(restored_cost_basis)
+            .ok_or(ErrorCode::MathOverflow)?;
+        fund_pool.pending_cashout = fund_pool.pending_cashout
+            .checked_sub(locked_usdc_value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        // Free the redemption gate capacity this ticket was holding, unless its window has already rolled over
+        if unstake_ticket.release_window_start == fund_pool.redemption_window_start {
+            fund_pool.redemption_window_queued = fund_pool.redemption_window_queued.saturating_sub(locked_usdc_value);
+        }
+
+        // USDC already set aside for this ticket goes back to the trading vault
+        let surplus_funding = fund_pool.pending_cashout_funded.saturating_sub(fund_pool.pending_cashout);
+        if surplus_funding > 0 {
+            let pending_cashout_seeds = &[
+                b"pending_cashout_pool_v2".as_ref(),
+                &[pending_cashout_pool.bump],
+            ];
+            token_interface::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    InterfaceTransfer {
+                        from: ctx.accounts.pending_cashout_usdc_account.to_account_info(),
+                        to: ctx.accounts.trading_vault.to_account_info(),
+                        authority: pending_cashout_pool.to_account_info(),
+                    },
+                    &[pending_cashout_seeds],
+                ),
+                surplus_funding,
+            )?;
+            fund_pool.pending_cashout_funded = fund_pool.pending_cashout;
+        }
+
+        // Re-mint share tokens for the restored shares
+        mint_share_tokens(
+            &ctx.accounts.share_token_program,
+            &ctx.accounts.share_mint,
+            &ctx.accounts.user_share_token_account,
+            fund_pool,
+            restored_shares,
+        )?;
+
+        // Remove this ticket from pending cashout pool
+        let user_key = ctx.accounts.user.key();
+        pending_cashout_pool.users.retain(|u| !(u.user == user_key && u.nonce == nonce));
+
+        // Add restored shares to any still-active position
+        user_share.shares = user_share.shares
+            .checked_add(restored_shares)
+            .ok_or(ErrorCode::MathOverflow)?;
+        user_share.pending_tickets = user_share.pending_tickets
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        if let Some(user_info) = registry_user_info_mut(user_registry, user_share) {
+            user_info.shares = user_share.shares;
+            registry_remove_pending_unstake(user_info, original_shares, locked_usdc_value, user_share.pending_tickets);
+        }
+
+        msg!("Unstake ticket {} cancelled: {} USDC re-staked for {} shares (originally {})",
+             nonce, locked_usdc_value, restored_shares, original_shares);
+        Ok(())
+    }
+
+    // Owner redirects an unpaid ticket; the new account also becomes the default for future requests
+    pub fn update_unstake_destination(ctx: Context<UpdateUnstakeDestination>, nonce: u64) -> Result<()> {
+        let destination = ctx.accounts.destination_usdc_account.key();
+        ctx.accounts.unstake_ticket.destination = destination;
+        ctx.accounts.user_share.withdrawal_destination = destination;
+
+        msg!("Unstake ticket {} will pay {}", nonce, destination);
+        Ok(())
+    }
+
+    // Pays a matured ticket to the destination registered at initiation. Anyone may call
+    // this once the ticket has matured, so keepers can settle on behalf of users.
+    pub fn complete_unstake(ctx: Context<CompleteUnstake>, nonce: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_COMPLETE_UNSTAKE)?;
+
+        let user_share = &mut ctx.accounts.user_share;
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
+        let user_registry = &mut ctx.accounts.user_registry;
+        let unstake_ticket = &ctx.accounts.unstake_ticket;
+        let clock = Clock::get()?;
+
+        // Check ticket waiting period
+        require!(
+            clock.unix_timestamp >= unstake_ticket.maturity_timestamp,
+            ErrorCode::UnstakeNotReady
+        );
+
+        // Use the locked USDC value from initiation
+        let final_usdc_amount = unstake_ticket.usdc_value;
+        require!(final_usdc_amount > 0, ErrorCode::InsufficientFundValue);
+
+        // Transfer USDC from pending cashout pool PDA to the registered destination
+        let pending_cashout_seeds = &[
+            b"pending_cashout_pool_v2".as_ref(),
+            &[ctx.bumps.pending_cashout_pool],
+        ];
+        token_interface::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                InterfaceTransfer {
+                    from: ctx.accounts.pending_cashout_usdc_account.to_account_info(),
+                    to: ctx.accounts.destination_usdc_account.to_account_info(),
+                    authority: pending_cashout_pool.to_account_info(),
+                },
+                &[pending_cashout_seeds],
+            ),
+            final_usdc_amount,
+        )?;
+
+        settle_unstake_ticket(unstake_ticket, user_share, user_registry, fund_pool, pending_cashout_pool)?;
+
+        msg!("Unstake ticket {} completed: {} USDC transferred to {}, {} shares remaining",
+             nonce, final_usdc_amount, unstake_ticket.destination, user_share.shares);
+        Ok(())
+    }
+
+    // Permissionless keeper crank: pays every matured ticket passed in remaining_accounts as
+    // groups of [UnstakeTicket, UserShare, UserRegistry, destination token account, user wallet].
+    // Immature tickets are skipped; the batch stops once the pending cashout vault runs dry.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn settle_matured_unstakes<'info>(ctx: Context<'_, '_, 'info, 'info, SettleMaturedUnstakes<'info>>) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_COMPLETE_UNSTAKE)?;
+        require!(
+            !ctx.remaining_accounts.is_empty() && ctx.remaining_accounts.len() % SETTLE_ACCOUNTS_PER_TICKET == 0,
+            ErrorCode::InvalidRemainingAccounts
+        );
+
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let pending_cashout_pool = &mut ctx.accounts.pending_cashout_pool;
+        let clock = Clock::get()?;
+
+        let pending_cashout_bump = pending_cashout_pool.bump;
+        let pending_cashout_seeds = &[
+            b"pending_cashout_pool_v2".as_ref(),
+            &[pending_cashout_bump],
+        ];
+
+        let mut available = ctx.accounts.pending_cashout_usdc_account.amount;
+        let mut settled_count: u64 = 0;
+        let mut settled_usdc: u64 = 0;
+
+        for group in ctx.remaining_accounts.chunks(SETTLE_ACCOUNTS_PER_TICKET) {
+            let [ticket_info, user_share_info, user_registry_info, destination_info, user_info] = group else {
+                return Err(ErrorCode::InvalidRemainingAccounts.into());
+            };
+
+            let unstake_ticket: Account<UnstakeTicket> = Account::try_from(ticket_info)?;
+            if clock.unix_timestamp < unstake_ticket.maturity_timestamp {
+                msg!("Skipping ticket {} for {}: matures at {}",
+                     unstake_ticket.nonce, unstake_ticket.user, unstake_ticket.maturity_timestamp);
+                continue;
+            }
+            if unstake_ticket.usdc_value > available {
+                msg!("Pending cashout vault exhausted: {} USDC available, {} USDC needed", available, unstake_ticket.usdc_value);
+                break;
+            }
+
+            // Each group must belong to the ticket's owner
+            require_keys_eq!(user_info.key(), unstake_ticket.user, ErrorCode::InvalidRemainingAccounts);
+            require_keys_eq!(destination_info.key(), unstake_ticket.destination, ErrorCode::InvalidDestination);
+            let (expected_user_share, _) = Pubkey::find_program_address(
+                &[b"user_share_v2", unstake_ticket.user.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(user_share_info.key(), expected_user_share, ErrorCode::InvalidRemainingAccounts);
+
+            let mut user_share: Account<UserShare> = Account::try_from(user_share_info)?;
+            let mut user_registry: Account<UserRegistry> = Account::try_from(user_registry_info)?;
+            require!(user_registry.registry_id == user_share.registry_id, ErrorCode::InvalidRegistryIndex);
+
+            let final_usdc_amount = unstake_ticket.usdc_value;
+            token_interface::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    InterfaceTransfer {
+                        from: ctx.accounts.pending_cashout_usdc_account.to_account_info(),
+                        to: destination_info.clone(),
+                        authority: pending_cashout_pool.to_account_info(),
+                    },
+                    &[pending_cashout_seeds],
+                ),
+                final_usdc_amount,
+            )?;
+
+            settle_unstake_ticket(&unstake_ticket, &mut user_share, &mut user_registry, fund_pool, pending_cashout_pool)?;
+
+            // Persist now so later groups that reuse these accounts see the update
+            user_share.exit(&crate::ID)?;
+            user_registry.exit(&crate::ID)?;
+            unstake_ticket.close(user_info.clone())?;
+
+            available -= final_usdc_amount;
+            settled_count += 1;
+            settled_usdc = settled_usdc.checked_add(final_usdc_amount).ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        msg!("Settled {} matured unstakes for {} USDC. Pending cashout remaining: {} USDC",
+             settled_count, settled_usdc, fund_pool.pending_cashout);
+        Ok(())
+    }
+
+    pub fn update_optimized_nav(ctx: Context<UpdateOptimizedNav>, new_portfolio_value: u64) -> Result<()> {
+        // Only gardener (Lambda) can update NAV - enforced by config has_one
+        require_not_paused(&ctx.accounts.config, PAUSE_NAV_UPDATE)?;
+        // Once a reporter quorum is configured NAV only moves through finalize_nav
+        require!(ctx.accounts.config.nav_quorum == 0, ErrorCode::NavQuorumRequired);
+
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let nav_history = &mut ctx.accounts.nav_history;
+        let pending_cashout_pool = &ctx.accounts.pending_cashout_pool;
+        let clock = Clock::get()?;
+
+        // Settle management fee for the period since the last accrual before repricing
+        settle_management_fee(
+            fund_pool,
+            &ctx.accounts.config,
+            &mut ctx.accounts.fee_settlement,
+            clock.unix_timestamp,
+        )?;
+
+        let applied = apply_portfolio_value_guarded(
+            fund_pool,
+            nav_history,
+            pending_cashout_pool,
+            &ctx.accounts.config,
+            new_portfolio_value,
+            clock.unix_timestamp,
+        )?;
+
+        if let Some(final_optimized_nav) = applied {
+            msg!("NAV updated: Optimized {} USDC, Real {} USDC", 
+                 final_optimized_nav, new_portfolio_value);
+        }
+        Ok(())
+    }
+
+    // Second key signs off on a NAV move that exceeded max_nav_change_bps.
+    // expected_value must match the parked value so a newer proposal cannot be confirmed blindly.
+    pub fn confirm_nav_update(ctx: Context<ConfirmNavUpdate>, expected_value: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_NAV_UPDATE)?;
+
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let clock = Clock::get()?;
+
+        require!(fund_pool.pending_nav_timestamp != 0, ErrorCode::NoPendingNavUpdate);
+        require!(fund_pool.pending_nav_value == expected_value, ErrorCode::PendingNavMismatch);
+        // A value parked before a later in-band update would rewind NAV
+        require!(
+            fund_pool.pending_nav_timestamp >= fund_pool.last_nav_update_timestamp,
+            ErrorCode::SupersededNavUpdate
+        );
+        // A parked value is only good for as long as a regular NAV would be
+        if ctx.accounts.config.max_nav_staleness_seconds > 0 {
+            let expires = fund_pool.pending_nav_timestamp
+                .checked_add(ctx.accounts.config.max_nav_staleness_seconds)
+                .ok_or(ErrorCode::MathOverflow)?;
+            require!(clock.unix_timestamp <= expires, ErrorCode::StaleNav);
+        }
+
+        let new_portfolio_value = fund_pool.pending_nav_value;
+        fund_pool.pending_nav_value = 0;
+        fund_pool.pending_nav_timestamp = 0;
+
+        let final_optimized_nav = apply_portfolio_value(
+            fund_pool,
+            &mut ctx.accounts.nav_history,
+            &ctx.accounts.pending_cashout_pool,
+            new_portfolio_value,
+            clock.unix_timestamp,
+            ctx.accounts.config.nav_window_seconds,
+        )?;
+
+        msg!("NAV move confirmed by {}: Optimized {} USDC, Real {} USDC",
+             ctx.accounts.nav_confirm_authority.key(), final_optimized_nav, new_portfolio_value);
+        Ok(())
+    }
+
+    // Admin replaces the NAV reporter set. Starts a new epoch so submissions from the old set are discarded.
+    pub fn set_nav_reporters(ctx: Context<SetNavReporters>, reporters: Vec<Pubkey>) -> Result<()> {
+        require!(reporters.len() <= MAX_NAV_REPORTERS, ErrorCode::InvalidConfig);
+        for (i, reporter) in reporters.iter().enumerate() {
+            require!(!reporters[..i].contains(reporter), ErrorCode::InvalidConfig);
+        }
+
+        let nav_reporters = &mut ctx.accounts.nav_reporters;
+        nav_reporters.reporters = reporters;
+        nav_reporters.current_epoch = nav_reporters.current_epoch
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        nav_reporters.bump = ctx.bumps.nav_reporters;
+
+        msg!("NAV reporter set updated: {} reporters, epoch {}",
+             nav_reporters.reporters.len(), nav_reporters.current_epoch);
+        Ok(())
+    }
+
+    // A registered reporter posts its portfolio valuation for the current epoch.
+    // Resubmitting within the same epoch replaces the reporter's previous value.
+    pub fn submit_nav(ctx: Context<SubmitNav>, epoch: u64, portfolio_value: u64) -> Result<()> {
+        let nav_reporters = &ctx.accounts.nav_reporters;
+        let nav_submission = &mut ctx.accounts.nav_submission;
+        let reporter = ctx.accounts.reporter.key();
+        let clock = Clock::get()?;
+
+        require!(epoch == nav_reporters.current_epoch, ErrorCode::NavEpochMismatch);
+        require!(nav_reporters.reporters.contains(&reporter), ErrorCode::UnauthorizedNavReporter);
+        require!(!nav_submission.finalized, ErrorCode::NavEpochFinalized);
+
+        nav_submission.epoch = epoch;
+        nav_submission.bump = ctx.bumps.nav_submission;
+
+        let report = NavReport {
+            reporter,
+            portfolio_value,
+            timestamp: clock.unix_timestamp,
+        };
+        match nav_submission.reports.iter_mut().find(|r| r.reporter == reporter) {
+            Some(existing) => *existing = report,
+            None => nav_submission.reports.push(report),
+        }
+
+        msg!("NAV submitted for epoch {}: {} USDC by {} ({} reports)",
+             epoch, portfolio_value, reporter, nav_submission.reports.len());
+        Ok(())
+    }
+
+    // Permissionless crank: once a quorum of reports sits within the tolerance band around the
+    // median, the median becomes the fund's portfolio value and the next epoch opens.
+    pub fn finalize_nav(ctx: Context<FinalizeNav>, epoch: u64) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_NAV_UPDATE)?;
+
+        let config = &ctx.accounts.config;
+        let nav_reporters = &mut ctx.accounts.nav_reporters;
+        let nav_submission = &mut ctx.accounts.nav_submission;
+        let clock = Clock::get()?;
+
+        require!(config.nav_quorum > 0, ErrorCode::InvalidConfig);
+        require!(epoch == nav_reporters.current_epoch, ErrorCode::NavEpochMismatch);
+        require!(!nav_submission.finalized, ErrorCode::NavEpochFinalized);
+
+        // Only reports from currently registered reporters count
+        let reports: Vec<NavReport> = nav_submission.reports.iter()
+            .filter(|r| nav_reporters.reporters.contains(&r.reporter))
+            .cloned()
+            .collect();
+        require!(reports.len() >= config.nav_quorum as usize, ErrorCode::NavQuorumNotReached);
+
+        let mut values: Vec<u64> = reports.iter().map(|r| r.portfolio_value).collect();
+        let median = calculate_median(&mut values)?;
+
+        let mut agreeing: u8 = 0;
+        for report in &reports {
+            if is_within_tolerance(report.portfolio_value, median, config.nav_tolerance_bps) {
+                agreeing += 1;
+            } else {
+                msg!("NAV outlier in epoch {}: {} reported {} USDC vs median {} USDC",
+                     epoch, report.reporter, report.portfolio_value, median);
+            }
+        }
+        require!(agreeing >= config.nav_quorum, ErrorCode::NavQuorumNotReached);
+
+        // Settle management fee for the period since the last accrual before repricing
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        settle_management_fee(
+            fund_pool,
+            config,
+            &mut ctx.accounts.fee_settlement,
+            clock.unix_timestamp,
+        )?;
+
+        let applied = apply_portfolio_value_guarded(
+            fund_pool,
+            &mut ctx.accounts.nav_history,
+            &ctx.accounts.pending_cashout_pool,
+            config,
+            median,
+            clock.unix_timestamp,
+        )?;
+
+        nav_submission.finalized = true;
+        nav_submission.finalized_value = median;
+        nav_reporters.current_epoch = epoch
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        if let Some(final_optimized_nav) = applied {
+            msg!("NAV epoch {} finalized: {} of {} reports agree. Optimized {} USDC, Real {} USDC",
+                 epoch, agreeing, reports.len(), final_optimized_nav, median);
+        } else {
+            msg!("NAV epoch {} finalized at {} USDC pending confirmation", epoch, median);
+        }
+        Ok(())
+    }
+
+    // Admin registers how a trading wallet holding is priced. compute_nav must cover every registered mint.
+    pub fn register_price_feed(ctx: Context<RegisterPriceFeed>, kind: OracleKind, feed_id: [u8; 32]) -> Result<()> {
+        require!(cfg!(feature = "mock-oracle") || kind != OracleKind::Mock, ErrorCode::MockOracleDisabled);
+
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.mint = ctx.accounts.mint.key();
+        price_feed.kind = kind;
+        price_feed.feed_id = feed_id;
+        price_feed.token_decimals = ctx.accounts.mint.decimals;
+        price_feed.bump = ctx.bumps.price_feed;
+
+        let config = &mut ctx.accounts.config;
+        config.price_feed_count = config.price_feed_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        msg!("Price feed registered for {}: {:?}. {} feeds registered",
+             price_feed.mint, kind, config.price_feed_count);
+        Ok(())
+    }
+
+    pub fn remove_price_feed(ctx: Context<RemovePriceFeed>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.price_feed_count = config.price_feed_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        msg!("Price feed removed for {}. {} feeds registered",
+             ctx.accounts.price_feed.mint, config.price_feed_count);
+        Ok(())
+    }
+
+    // Admin-set price for localnet tests; read through MockOracle
+    #[cfg(feature = "mock-oracle")]
+    pub fn set_mock_price(ctx: Context<SetMockPrice>, price: i64, expo: i32) -> Result<()> {
+        let mock_feed = &mut ctx.accounts.mock_price_feed;
+        mock_feed.mint = ctx.accounts.mint.key();
+        mock_feed.price = price;
+        mock_feed.expo = expo;
+        mock_feed.publish_time = Clock::get()?.unix_timestamp;
+        mock_feed.bump = ctx.bumps.mock_price_feed;
+
+        msg!("Mock price for {}: {} x 10^{}", mock_feed.mint, price, expo);
+        Ok(())
+    }
+
+    // Gardener-run on-chain valuation: vault + liquidity buffer + every registered trading wallet
+    // holding (passed in remaining_accounts as [token account, PriceFeedConfig, price account]),
+    // less cashouts not yet moved to the pending cashout vault.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn compute_nav<'info>(ctx: Context<'_, '_, 'info, 'info, ComputeNav<'info>>) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_NAV_UPDATE)?;
+        // Once a reporter quorum is configured NAV only moves through finalize_nav
+        require!(ctx.accounts.config.nav_quorum == 0, ErrorCode::NavQuorumRequired);
+        require!(ctx.accounts.config.price_feed_count >= 1, ErrorCode::IncompleteHoldings);
+        require!(
+            ctx.remaining_accounts.len() % COMPUTE_NAV_ACCOUNTS_PER_HOLDING == 0,
+            ErrorCode::InvalidRemainingAccounts
+        );
+
+        let config = &ctx.accounts.config;
+        let clock = Clock::get()?;
+        let usdc_decimals = ctx.accounts.usdc_mint.decimals;
+
+        let mut total_value = ctx.accounts.trading_vault.amount
+            .checked_add(ctx.accounts.liquidity_buffer_vault.amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let mut priced_mints: Vec<Pubkey> = Vec::new();
+        for group in ctx.remaining_accounts.chunks(COMPUTE_NAV_ACCOUNTS_PER_HOLDING) {
+            let [token_account_info, price_feed_info, price_account_info] = group else {
+                return Err(ErrorCode::InvalidRemainingAccounts.into());
+            };
+
+            let price_feed: Account<PriceFeedConfig> = Account::try_from(price_feed_info)?;
+            require!(!priced_mints.contains(&price_feed.mint), ErrorCode::InvalidRemainingAccounts);
+            priced_mints.push(price_feed.mint);
+
+            // Holdings are read from the trading wallet's associated token account only
+            let token_account: InterfaceAccount<InterfaceTokenAccount> = InterfaceAccount::try_from(token_account_info)?;
+            require_keys_eq!(token_account.mint, price_feed.mint, ErrorCode::InvalidRemainingAccounts);
+            require_keys_eq!(
+                token_account_info.key(),
+                get_associated_token_address_with_program_id(&config.trading_wallet, &price_feed.mint, token_account_info.owner),
+                ErrorCode::InvalidRemainingAccounts
+            );
+
+            let oracle_price = read_oracle_price(price_account_info, &price_feed, clock.unix_timestamp)?;
+            require!(
+                clock.unix_timestamp.saturating_sub(oracle_price.publish_time) <= config.oracle_max_age_seconds,
+                ErrorCode::StaleOraclePrice
+            );
+
+            let holding_value = calculate_holding_value(
+                token_account.amount,
+                &oracle_price,
+                price_feed.token_decimals,
+                usdc_decimals,
+            )?;
+            total_value = total_value.checked_add(holding_value).ok_or(ErrorCode::MathOverflow)?;
+
+            msg!("Holding {}: {} units at {} x 10^{} = {} USDC",
+                 price_feed.mint, token_account.amount, oracle_price.price, oracle_price.expo, holding_value);
+        }
+        require!(priced_mints.len() == config.price_feed_count as usize, ErrorCode::IncompleteHoldings);
+
+        // Locked unstake value already left real_nav; only the unfunded part is still held above
+        let fund_pool = &mut ctx.accounts.fund_pool;
+        let unfunded_cashouts = fund_pool.pending_cashout.saturating_sub(fund_pool.pending_cashout_funded);
+        let portfolio_value = total_value.saturating_sub(unfunded_cashouts);
+
+        // Settle management fee for the period since the last accrual before repricing
+        settle_management_fee(
+            fund_pool,
+            config,
+            &mut ctx.accounts.fee_settlement,
+            clock.unix_timestamp,
+        )?;
+
+        let applied = apply_portfolio_value_guarded(
+            fund_pool,
+            &mut ctx.accounts.nav_history,
+            &ctx.accounts.pending_cashout_pool,
+            config,
+            portfolio_value,
+            clock.unix_timestamp,
+        )?;
+
+        if let Some(final_optimized_nav) = applied {
+            msg!("NAV computed from {} holdings: Optimized {} USDC, Real {} USDC",
+                 priced_mints.len(), final_optimized_nav, portfolio_value);
+        }
+        Ok(())
+    }
+
+    // Permissionless crank to accrue management fee between NAV updates
+    pub fn accrue_fees(ctx: Context<AccrueFees>) -> Result<()> {
+        let clock = Clock::get()?;
+
+        let fee_shares = settle_management_fee(
+            &mut ctx.accounts.fund_pool,
+            &ctx.accounts.config,
+            &mut ctx.accounts.fee_settlement,
+            clock.unix_timestamp,
+        )?;
+
+        let real_nav_per_share = calculate_real_nav_per_share(&ctx.accounts.fund_pool)?;
+        msg!("Fees accrued: {} shares. Gardener shares: {}. Real NAV: ${:.6}",
+             fee_shares, ctx.accounts.fee_settlement.gardener_share.shares,
+             real_nav_per_share as f64 / NAV_PRECISION as f64);
+        Ok(())
     }
 
     // View function to get user info
@@ -475,12 +2421,19 @@ pub mod shrub_fund {
         if user_share.shares > 0 {
             let current_value = calculate_usdc_for_shares(fund_pool, user_share.shares)?;
             let optimized_nav_per_share = calculate_optimized_nav_per_share(fund_pool)?;
+            let cost_basis = calculate_cost_of_shares(user_share.shares, user_share.cost_basis_per_share)?;
+            let unrealized_pnl = current_value as i128 - cost_basis as i128;
             
             msg!("User Share Info:");
             msg!("  User: {}", user_share.user);
             msg!("  Shares: {}", user_share.shares);
             msg!("  Current Value: ${:.2} USDC", current_value as f64 / NAV_PRECISION as f64);
             msg!("  NAV per Share: ${:.6}", optimized_nav_per_share as f64 / NAV_PRECISION as f64);
+            msg!("  Cost Basis: ${:.2} USDC (${:.6} per share)",
+                 cost_basis as f64 / NAV_PRECISION as f64,
+                 user_share.cost_basis_per_share as f64 / NAV_PRECISION as f64);
+            msg!("  Unrealized P&L: ${:.2} USDC", unrealized_pnl as f64 / NAV_PRECISION as f64);
+            msg!("  Pending Unstake Tickets: {}", user_share.pending_tickets);
             msg!("  Registry ID: {}", user_share.registry_id);
             msg!("  Registry Index: {}", user_share.registry_index);
         } else {
@@ -492,43 +2445,61 @@ pub mod shrub_fund {
 
     // Monthly commission collection (triggered by Lambda)
     pub fn collect_monthly_commission(ctx: Context<CollectMonthlyCommission>) -> Result<()> {
+        require_not_paused(&ctx.accounts.config, PAUSE_COMMISSION)?;
+
         let fund_pool = &mut ctx.accounts.fund_pool;
+        let commission_bps = ctx.accounts.config.commission_bps;
+        let commission_period_seconds = ctx.accounts.config.commission_period_seconds;
         let current_time = Clock::get()?.unix_timestamp;
         
+        // 0. Enforce one collection per commission period
+        if fund_pool.last_commission_timestamp != 0 {
+            let next_collection = fund_pool.last_commission_timestamp
+                .checked_add(commission_period_seconds)
+                .ok_or(ErrorCode::MathOverflow)?;
+            require!(current_time >= next_collection, ErrorCode::CommissionPeriodNotElapsed);
+        }
+        fund_pool.last_commission_timestamp = current_time;
+        
         msg!("📊 Monthly Commission Collection - Timestamp: {}", current_time);
         msg!("💰 Fund Status Check:");
         msg!("   Real NAV: {} USDC", fund_pool.real_nav);
-        msg!("   Total Shares: {} (represents {} USDC invested)", fund_pool.total_shares, fund_pool.total_shares);
+        msg!("   Total Shares: {}", fund_pool.total_shares);
         msg!("   Total Users: {}", fund_pool.total_users);
+        msg!("   High-Water Mark: ${:.6} per share", fund_pool.high_water_nav_per_share as f64 / NAV_PRECISION as f64);
         
-        // 1. Check if fund is profitable using real_nav (current portfolio value)
-        if fund_pool.real_nav <= fund_pool.total_shares {
-            let current_share_price = if fund_pool.total_shares > 0 {
-                (fund_pool.real_nav as u128 * NAV_PRECISION as u128 / fund_pool.total_shares as u128) as u64
-            } else {
-                NAV_PRECISION
-            };
-            
-            msg!("⏸️ No profit in fund - Current share price: ${:.6}", 
+        // 1. Only charge when NAV per share is above the high-water mark
+        let current_share_price = calculate_real_nav_per_share(fund_pool)?;
+        if current_share_price <= fund_pool.high_water_nav_per_share {
+            msg!("⏸️ No new high - Current share price: ${:.6}", 
                  current_share_price as f64 / NAV_PRECISION as f64);
-            msg!("⏸️ No commission collected. Fund value ({}) ≤ Total invested ({})", 
-                 fund_pool.real_nav, fund_pool.total_shares);
+            msg!("⏸️ No commission collected. Share price ≤ High-water mark (${:.6})", 
+                 fund_pool.high_water_nav_per_share as f64 / NAV_PRECISION as f64);
             return Ok(());
         }
         
-        // 2. Calculate total profit (current value - original investments)
+        // 2. Fund must also be above what holders actually paid (aggregate cost basis)
         let total_current_value = fund_pool.real_nav;
-        let total_invested = fund_pool.total_shares; // Each share = 1 USDC originally invested
+        if total_current_value <= fund_pool.total_cost_basis {
+            msg!("⏸️ No commission collected. Fund value ({}) ≤ Total cost basis ({})", 
+                 total_current_value, fund_pool.total_cost_basis);
+            return Ok(());
+        }
         
-        let total_profit = total_current_value
-            .checked_sub(total_invested)
-            .ok_or(ErrorCode::MathOverflow)?;
+        // 3. Profit = gains above the high-water mark, capped at gains above cost basis
+        let profit_above_high_water = ((current_share_price - fund_pool.high_water_nav_per_share) as u128)
+            .checked_mul(fund_pool.total_shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(NAV_PRECISION as u128)
+            .ok_or(ErrorCode::MathOverflow)? as u64;
+        let profit_above_cost_basis = total_current_value - fund_pool.total_cost_basis;
+        let total_profit = profit_above_high_water.min(profit_above_cost_basis);
         
-        // 3. Calculate 2% commission on total profit only (200 basis points)
+        // 4. Calculate commission on total profit only (configured basis points, 200 = 2%)
         let commission = (total_profit as u128)
-            .checked_mul(200)  // 2% = 200 basis points
+            .checked_mul(commission_bps as u128)
             .ok_or(ErrorCode::MathOverflow)?
-            .checked_div(10000) // Convert basis points to percentage
+            .checked_div(BPS_DENOMINATOR as u128) // Convert basis points to percentage
             .ok_or(ErrorCode::MathOverflow)? as u64;
         
         if commission == 0 {
@@ -536,11 +2507,11 @@ pub mod shrub_fund {
             return Ok(());
         }
         
-        // 4. Check available balance in trading wallet
-        let available_balance = ctx.accounts.trading_usdc_account.amount;
+        // 5. Check available balance in trading vault
+        let available_balance = ctx.accounts.trading_vault.amount;
         
         if available_balance < commission {
-            msg!("⚠️ Insufficient trading wallet balance for commission");
+            msg!("⚠️ Insufficient trading vault balance for commission");
             msg!("   Commission due: {} USDC", commission);
             msg!("   Available balance: {} USDC", available_balance);
             return Err(ErrorCode::InsufficientFunds.into());
@@ -548,11 +2519,13 @@ pub mod shrub_fund {
         
         msg!("💰 Commission Calculation:");
         msg!("   Total Fund Value (real_nav): {} USDC", total_current_value);
-        msg!("   Total Originally Invested: {} USDC", total_invested);
-        msg!("   Total Profit: {} USDC", total_profit);
-        msg!("   Commission (2% of profit): {} USDC", commission);
+        msg!("   Total Cost Basis: {} USDC", fund_pool.total_cost_basis);
+        msg!("   Profit Above High-Water Mark: {} USDC", profit_above_high_water);
+        msg!("   Profit Above Cost Basis: {} USDC", profit_above_cost_basis);
+        msg!("   Chargeable Profit: {} USDC", total_profit);
+        msg!("   Commission ({} bps of profit): {} USDC", commission_bps, commission);
         
-        // 5. Calculate share price impact for transparency
+        // 6. Calculate share price impact for transparency
         let pre_commission_share_price = if fund_pool.total_shares > 0 {
             (total_current_value as u128 * NAV_PRECISION as u128 / fund_pool.total_shares as u128) as u64
         } else {
@@ -573,20 +2546,25 @@ pub mod shrub_fund {
         msg!("   Before Commission: ${:.6}", pre_commission_share_price as f64 / NAV_PRECISION as f64);
         msg!("   After Commission: ${:.6}", post_commission_share_price as f64 / NAV_PRECISION as f64);
         
-        // 6. Transfer commission from trading wallet to gardener
+        // 7. Transfer commission from trading vault to gardener (fund_pool PDA signs)
+        let fund_pool_seeds = &[
+            b"optimized_fund_pool_v2".as_ref(),
+            &[fund_pool.bump],
+        ];
         token_interface::transfer(
-            CpiContext::new(
+            CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
                 InterfaceTransfer {
-                    from: ctx.accounts.trading_usdc_account.to_account_info(),
+                    from: ctx.accounts.trading_vault.to_account_info(),
                     to: ctx.accounts.gardener_usdc_account.to_account_info(),
-                    authority: ctx.accounts.trading_wallet.to_account_info(),
+                    authority: fund_pool.to_account_info(),
                 },
+                &[fund_pool_seeds],
             ),
             commission,
         )?;
         
-        // 7. Update fund NAVs to reflect commission payment
+        // 8. Update fund NAVs to reflect commission payment
         // Both real_nav and optimized_nav are reduced by commission
         fund_pool.real_nav = fund_pool.real_nav
             .checked_sub(commission)
@@ -596,10 +2574,14 @@ pub mod shrub_fund {
             .checked_sub(commission)
             .ok_or(ErrorCode::MathOverflow)?;
         
+        // 9. Raise the high-water mark so the same gains are never charged twice
+        fund_pool.high_water_nav_per_share = post_commission_share_price;
+        
         msg!("✅ SUCCESS: Monthly commission collected!");
         msg!("   💸 {} USDC transferred to Gardener", commission);
         msg!("   📊 Updated Real NAV: {} USDC", fund_pool.real_nav);
         msg!("   📊 Updated Optimized NAV: {} USDC", fund_pool.optimized_nav);
+        msg!("   🏔️ New High-Water Mark: ${:.6} per share", post_commission_share_price as f64 / NAV_PRECISION as f64);
         msg!("   🎯 Commission represents {:.3}% of total fund value", 
              commission as f64 / total_current_value as f64 * 100.0);
         
@@ -611,11 +2593,419 @@ pub mod shrub_fund {
 
 
 // Main program contexts
+#[derive(Accounts)]
+pub struct InitializeFund<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    // Only the program upgrade authority can bootstrap a deployment
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::ShrubFund>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ ErrorCode::UnauthorizedAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub usdc_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"fund_config_v2"],
+        bump,
+        space = 8 + FundConfig::INIT_SPACE
+    )]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"optimized_fund_pool_v2"],
+        bump,
+        space = 8 + FundPool::INIT_SPACE
+    )]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"nav_history_v2"],
+        bump,
+        space = 8 + NavHistory::INIT_SPACE
+    )]
+    pub nav_history: Account<'info, NavHistory>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"pending_cashout_pool_v2"],
+        bump,
+        space = 8 + PendingCashoutPool::INIT_SPACE
+    )]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"registry_directory_v2"],
+        bump,
+        space = 8 + RegistryDirectory::INIT_SPACE
+    )]
+    pub registry_directory: Account<'info, RegistryDirectory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFundConfig<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTradingVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(address = config.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"trading_vault_v2"],
+        bump,
+        token::mint = usdc_mint,
+        token::authority = fund_pool,
+        token::token_program = token_program
+    )]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeShareMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"share_mint_v2"],
+        bump,
+        mint::decimals = SHARE_DECIMALS,
+        mint::authority = fund_pool,
+        mint::token_program = share_token_program,
+        extensions::transfer_hook::authority = fund_pool,
+        extensions::transfer_hook::program_id = crate::ID
+    )]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    pub share_token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeShareHook<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(seeds = [b"share_mint_v2"], bump)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    /// CHECK: ExtraAccountMetaList TLV data, written by initialize_share_hook
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"extra-account-metas", share_mint.key().as_ref()],
+        bump,
+        space = ExtraAccountMetaList::size_of(SHARE_HOOK_EXTRA_ACCOUNTS)?
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Account order is fixed by the transfer hook interface: source, mint, destination,
+// owner, meta list, then the extras from share_hook_extra_account_metas()
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    #[account(token::mint = share_mint)]
+    pub source_token: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    #[account(seeds = [b"share_mint_v2"], bump)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    #[account(token::mint = share_mint)]
+    pub destination_token: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    /// CHECK: Source owner or delegate, already validated by Token-2022
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: ExtraAccountMetaList PDA, verified by seeds
+    #[account(seeds = [b"extra-account-metas", share_mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"user_share_v2", source_token.owner.as_ref()], bump)]
+    pub sender_share: Account<'info, UserShare>,
+
+    #[account(mut, seeds = [b"user_share_v2", destination_token.owner.as_ref()], bump)]
+    pub receiver_share: Account<'info, UserShare>,
+
+    #[account(mut, seeds = [b"user_registry_v2", sender_share.registry_id.to_le_bytes().as_ref()], bump)]
+    pub sender_registry: Account<'info, UserRegistry>,
+
+    #[account(mut, seeds = [b"user_registry_v2", receiver_share.registry_id.to_le_bytes().as_ref()], bump)]
+    pub receiver_registry: Account<'info, UserRegistry>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    /// CHECK: Receiver allowlist PDA, verified by seeds; may be uninitialized when the receiver was never approved
+    #[account(seeds = [b"allowlist_v2", destination_token.owner.as_ref()], bump)]
+    pub receiver_allowlist_entry: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePendingCashoutVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+
+    #[account(address = config.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"pending_cashout_vault_v2"],
+        bump,
+        token::mint = usdc_mint,
+        token::authority = pending_cashout_pool,
+        token::token_program = token_program
+    )]
+    pub pending_cashout_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundPendingCashouts<'info> {
+    pub gardener: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    #[account(seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+
+    #[account(mut, seeds = [b"pending_cashout_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = pending_cashout_pool)]
+    pub pending_cashout_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLiquidityBufferVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(address = config.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, InterfaceMint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"liquidity_buffer_vault_v2"],
+        bump,
+        token::mint = usdc_mint,
+        token::authority = fund_pool,
+        token::token_program = token_program
+    )]
+    pub liquidity_buffer_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundLiquidityBuffer<'info> {
+    pub gardener: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    #[account(mut, seeds = [b"liquidity_buffer_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub liquidity_buffer_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToTradingWallet<'info> {
+    pub gardener: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    /// CHECK: Trading wallet - verified by address constraint
+    #[account(address = config.trading_wallet)]
+    pub trading_wallet: UncheckedAccount<'info>,
+
+    #[account(mut, token::mint = config.usdc_mint, token::authority = trading_wallet)]
+    pub trading_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToTradingVault<'info> {
+    #[account(address = config.trading_wallet @ ErrorCode::UnauthorizedAuthority)]
+    pub trading_wallet: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    #[account(mut, token::mint = config.usdc_mint, token::authority = trading_wallet)]
+    pub trading_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauseFlags<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct ApproveAllowlist<'info> {
+    #[account(mut)]
+    pub compliance_authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = compliance_authority @ ErrorCode::UnauthorizedCompliance)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = compliance_authority,
+        seeds = [b"allowlist_v2", user.as_ref()],
+        bump,
+        space = 8 + AllowlistEntry::INIT_SPACE
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct RevokeAllowlist<'info> {
+    pub compliance_authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = compliance_authority @ ErrorCode::UnauthorizedCompliance)]
+    pub config: Account<'info, FundConfig>,
+
+    #[account(mut, seeds = [b"allowlist_v2", user.as_ref()], bump = allowlist_entry.bump)]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+}
+
 #[derive(Accounts)]
 #[instruction(registry_id: u64)]
 pub struct InitializeUserRegistry<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
+
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
     
     #[account(
         mut,
@@ -641,87 +3031,439 @@ pub struct InitializeUserRegistry<'info> {
 pub struct StakeUsdc<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
-    #[account(mut, token::mint = USDC_MINT, token::authority = user)]
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, token::mint = config.usdc_mint, token::authority = user)]
     pub user_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
     #[account(
         init_if_needed,
-        payer = user,
-        seeds = [b"user_share_v2", user.key().as_ref()],
+        payer = user,
+        seeds = [b"user_share_v2", user.key().as_ref()],
+        bump,
+        space = 8 + UserShare::INIT_SPACE
+    )]
+    pub user_share: Account<'info, UserShare>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::authority = fund_pool, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::authority = user, token::token_program = share_token_program)]
+    pub user_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    
+    // Registry accounts (optional - for new users)
+    #[account(mut, seeds = [b"registry_directory_v2"], bump)]
+    pub registry_directory: Option<Account<'info, RegistryDirectory>>,
+    
+    #[account(mut)]
+    pub user_registry: Option<Account<'info, UserRegistry>>,
+
+    // Compliance attestation (required when config.allowlist_required)
+    #[account(seeds = [b"allowlist_v2", user.key().as_ref()], bump = allowlist_entry.bump)]
+    pub allowlist_entry: Option<Account<'info, AllowlistEntry>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferShares<'info> {
+    #[account(mut)]
+    pub sender: Signer<'info>,
+    /// CHECK: Recipient wallet - only used as a PDA seed and token account authority
+    pub recipient: UncheckedAccount<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"user_share_v2", sender.key().as_ref()], bump)]
+    pub sender_share: Account<'info, UserShare>,
+    #[account(
+        init_if_needed,
+        payer = sender,
+        seeds = [b"user_share_v2", recipient.key().as_ref()],
         bump,
         space = 8 + UserShare::INIT_SPACE
     )]
-    pub user_share: Account<'info, UserShare>,
-    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump)]
-    pub fund_pool: Account<'info, FundPool>,
-    /// CHECK: Trading wallet - verified by address constraint
-    #[account(address = TRADING_WALLET)]
-    pub trading_wallet: UncheckedAccount<'info>,
-    #[account(mut, token::mint = USDC_MINT, token::authority = trading_wallet)]
-    pub trading_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
-    pub token_program: Interface<'info, TokenInterface>,
+    pub recipient_share: Account<'info, UserShare>,
+    #[account(mut, constraint = sender_registry.registry_id == sender_share.registry_id @ ErrorCode::InvalidRegistryIndex)]
+    pub sender_registry: Account<'info, UserRegistry>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::authority = fund_pool, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::authority = sender, token::token_program = share_token_program)]
+    pub sender_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, token::mint = share_mint, token::authority = recipient, token::token_program = share_token_program)]
+    pub recipient_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub share_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
-    
-    // Registry accounts (optional - for new users)
+
+    // Recipient registry accounts (directory only needed for a new recipient)
     #[account(mut, seeds = [b"registry_directory_v2"], bump)]
     pub registry_directory: Option<Account<'info, RegistryDirectory>>,
-    
+
     #[account(mut)]
     pub user_registry: Option<Account<'info, UserRegistry>>,
+
+    // Recipient compliance attestation (required when config.allowlist_required)
+    #[account(seeds = [b"allowlist_v2", recipient.key().as_ref()], bump = recipient_allowlist_entry.bump)]
+    pub recipient_allowlist_entry: Option<Account<'info, AllowlistEntry>>,
 }
 
 #[derive(Accounts)]
 pub struct InitiateUnstake<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
     #[account(mut, seeds = [b"user_share_v2", user.key().as_ref()], bump)]
     pub user_share: Account<'info, UserShare>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"unstake_ticket_v2", user.key().as_ref(), user_share.next_ticket_nonce.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + UnstakeTicket::INIT_SPACE
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+    // Payout destination; may be owned by any wallet (cold wallet, exchange deposit).
+    // Omit to reuse the destination registered by an earlier request.
+    #[account(token::mint = config.usdc_mint)]
+    pub destination_usdc_account: Option<InterfaceAccount<'info, InterfaceTokenAccount>>,
     #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump)]
     pub fund_pool: Account<'info, FundPool>,
     #[account(
         mut,
         seeds = [b"pending_cashout_pool_v2"],
         bump,
-        realloc = 8 + PendingCashoutPool::INIT_SPACE + (pending_cashout_pool.users.len().saturating_sub(100)) * std::mem::size_of::<PendingUsers>(),
+        realloc = 8 + PendingCashoutPool::INIT_SPACE + ((pending_cashout_pool.users.len() + 1).saturating_sub(100)) * PendingUsers::INIT_SPACE,
         realloc::payer = user,
         realloc::zero = false
     )]
     pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
-    #[account(mut)]
+    #[account(mut, constraint = user_registry.registry_id == user_share.registry_id @ ErrorCode::InvalidRegistryIndex)]
     pub user_registry: Account<'info, UserRegistry>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::authority = fund_pool, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::authority = user, token::token_program = share_token_program)]
+    pub user_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub share_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CompleteUnstake<'info> {
+pub struct InstantUnstake<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"user_share_v2", user.key().as_ref()], bump)]
+    pub user_share: Account<'info, UserShare>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, constraint = user_registry.registry_id == user_share.registry_id @ ErrorCode::InvalidRegistryIndex)]
+    pub user_registry: Account<'info, UserRegistry>,
+    #[account(mut, seeds = [b"liquidity_buffer_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub liquidity_buffer_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+    // Payout destination; may be owned by any wallet (cold wallet, exchange deposit)
+    #[account(mut, token::mint = config.usdc_mint)]
+    pub destination_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::authority = fund_pool, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::authority = user, token::token_program = share_token_program)]
+    pub user_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CancelUnstake<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
-    #[account(mut, token::mint = USDC_MINT, token::authority = user)]
-    pub user_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"user_share_v2", user.key().as_ref()], bump)]
+    pub user_share: Account<'info, UserShare>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"unstake_ticket_v2", user.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump = unstake_ticket.bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+    #[account(mut, seeds = [b"pending_cashout_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = pending_cashout_pool)]
+    pub pending_cashout_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, constraint = user_registry.registry_id == user_share.registry_id @ ErrorCode::InvalidRegistryIndex)]
+    pub user_registry: Account<'info, UserRegistry>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::authority = fund_pool, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::authority = user, token::token_program = share_token_program)]
+    pub user_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct UpdateUnstakeDestination<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"user_share_v2", user.key().as_ref()], bump)]
+    pub user_share: Account<'info, UserShare>,
+    #[account(
+        mut,
+        seeds = [b"unstake_ticket_v2", user.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump = unstake_ticket.bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
+    // New payout destination; may be owned by any wallet
+    #[account(token::mint = config.usdc_mint)]
+    pub destination_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CompleteUnstake<'info> {
+    pub cranker: Signer<'info>,
+    /// CHECK: Ticket owner - verified by user_share and unstake_ticket seeds, receives ticket rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, address = unstake_ticket.destination @ ErrorCode::InvalidDestination, token::mint = config.usdc_mint)]
+    pub destination_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
     #[account(mut, seeds = [b"user_share_v2", user.key().as_ref()], bump)]
     pub user_share: Account<'info, UserShare>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"unstake_ticket_v2", user.key().as_ref(), nonce.to_le_bytes().as_ref()],
+        bump = unstake_ticket.bump
+    )]
+    pub unstake_ticket: Account<'info, UnstakeTicket>,
     #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump)]
     pub fund_pool: Account<'info, FundPool>,
     #[account(mut, seeds = [b"pending_cashout_pool_v2"], bump)]
     pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
-    #[account(mut, token::mint = USDC_MINT, token::authority = pending_cashout_pool)]
+    #[account(mut, seeds = [b"pending_cashout_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = pending_cashout_pool)]
     pub pending_cashout_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
-    #[account(mut)]
+    #[account(mut, constraint = user_registry.registry_id == user_share.registry_id @ ErrorCode::InvalidRegistryIndex)]
     pub user_registry: Account<'info, UserRegistry>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct SettleMaturedUnstakes<'info> {
+    pub cranker: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+    #[account(mut, seeds = [b"pending_cashout_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = pending_cashout_pool)]
+    pub pending_cashout_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
 #[derive(Accounts)]
 #[instruction(new_portfolio_value: u64)]
 pub struct UpdateOptimizedNav<'info> {
-    #[account(mut)]
     pub gardener: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
     #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump)]
     pub fund_pool: Account<'info, FundPool>,
     #[account(mut, seeds = [b"nav_history_v2"], bump)]
     pub nav_history: Account<'info, NavHistory>,
     #[account(seeds = [b"pending_cashout_pool_v2"], bump)]
     pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+    pub fee_settlement: FeeSettlement<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfirmNavUpdate<'info> {
+    pub nav_confirm_authority: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = nav_confirm_authority @ ErrorCode::UnauthorizedNavConfirmer)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"nav_history_v2"], bump = nav_history.bump)]
+    pub nav_history: Account<'info, NavHistory>,
+    #[account(seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetNavReporters<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"nav_reporters_v2"],
+        bump,
+        space = 8 + NavReporters::INIT_SPACE
+    )]
+    pub nav_reporters: Account<'info, NavReporters>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct SubmitNav<'info> {
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    #[account(seeds = [b"nav_reporters_v2"], bump = nav_reporters.bump)]
+    pub nav_reporters: Account<'info, NavReporters>,
+    #[account(
+        init_if_needed,
+        payer = reporter,
+        seeds = [b"nav_submission_v2", epoch.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + NavSubmission::INIT_SPACE
+    )]
+    pub nav_submission: Account<'info, NavSubmission>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct FinalizeNav<'info> {
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"nav_history_v2"], bump = nav_history.bump)]
+    pub nav_history: Account<'info, NavHistory>,
+    #[account(seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+    #[account(mut, seeds = [b"nav_reporters_v2"], bump = nav_reporters.bump)]
+    pub nav_reporters: Account<'info, NavReporters>,
+    #[account(mut, seeds = [b"nav_submission_v2", epoch.to_le_bytes().as_ref()], bump = nav_submission.bump)]
+    pub nav_submission: Account<'info, NavSubmission>,
+    pub fee_settlement: FeeSettlement<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterPriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    pub mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"price_feed_v2", mint.key().as_ref()],
+        bump,
+        space = 8 + PriceFeedConfig::INIT_SPACE
+    )]
+    pub price_feed: Account<'info, PriceFeedConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemovePriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"price_feed_v2", price_feed.mint.as_ref()], bump = price_feed.bump, close = authority)]
+    pub price_feed: Account<'info, PriceFeedConfig>,
+}
+
+#[cfg(feature = "mock-oracle")]
+#[derive(Accounts)]
+pub struct SetMockPrice<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump, has_one = authority @ ErrorCode::UnauthorizedAuthority)]
+    pub fund_pool: Account<'info, FundPool>,
+    pub mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"mock_price_feed_v2", mint.key().as_ref()],
+        bump,
+        space = 8 + MockPriceFeed::INIT_SPACE
+    )]
+    pub mock_price_feed: Account<'info, MockPriceFeed>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ComputeNav<'info> {
+    pub gardener: Signer<'info>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    #[account(mut, seeds = [b"nav_history_v2"], bump = nav_history.bump)]
+    pub nav_history: Account<'info, NavHistory>,
+    #[account(seeds = [b"pending_cashout_pool_v2"], bump = pending_cashout_pool.bump)]
+    pub pending_cashout_pool: Account<'info, PendingCashoutPool>,
+    #[account(address = config.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+    // Required so buffered USDC is always counted; initialize_liquidity_buffer_vault must run first
+    #[account(seeds = [b"liquidity_buffer_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub liquidity_buffer_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
+    pub fee_settlement: FeeSettlement<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AccrueFees<'info> {
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump)]
+    pub config: Account<'info, FundConfig>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
+    pub fee_settlement: FeeSettlement<'info>,
+}
+
+// Accounts that receive the management fee, shared by every instruction that settles it.
+// The gardener is identified by the share token account owner, which settle_management_fee
+// checks against config.gardener.
+#[derive(Accounts)]
+pub struct FeeSettlement<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds = [b"share_mint_v2"], bump, mint::token_program = share_token_program)]
+    pub share_mint: InterfaceAccount<'info, InterfaceMint>,
+    #[account(mut, token::mint = share_mint, token::token_program = share_token_program)]
+    pub gardener_share_token_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [b"user_share_v2", gardener_share_token_account.owner.as_ref()],
+        bump,
+        space = 8 + UserShare::INIT_SPACE
+    )]
+    pub gardener_share: Account<'info, UserShare>,
+    pub share_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    // Gardener registry accounts (required the first time fee shares are minted)
+    #[account(mut, seeds = [b"registry_directory_v2"], bump)]
+    pub registry_directory: Option<Account<'info, RegistryDirectory>>,
+
+    #[account(mut)]
+    pub user_registry: Option<Account<'info, UserRegistry>>,
 }
 
 #[derive(Accounts)]
@@ -735,20 +3477,19 @@ pub struct GetUserInfo<'info> {
 
 #[derive(Accounts)]
 pub struct CollectMonthlyCommission<'info> {
-    #[account(mut, constraint = gardener.key() == GARDENER_PUBKEY)]
+    #[account(mut)]
     pub gardener: Signer<'info>,
     
-    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = 254)]
-    pub fund_pool: Account<'info, FundPool>,
+    #[account(seeds = [b"fund_config_v2"], bump = config.bump, has_one = gardener @ ErrorCode::UnauthorizedGardener)]
+    pub config: Account<'info, FundConfig>,
     
-    /// CHECK: Trading wallet - verified by address constraint
-    #[account(address = TRADING_WALLET)]
-    pub trading_wallet: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"optimized_fund_pool_v2"], bump = fund_pool.bump)]
+    pub fund_pool: Account<'info, FundPool>,
     
-    #[account(mut, token::mint = USDC_MINT, token::authority = trading_wallet)]
-    pub trading_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
+    #[account(mut, seeds = [b"trading_vault_v2"], bump, token::mint = config.usdc_mint, token::authority = fund_pool)]
+    pub trading_vault: InterfaceAccount<'info, InterfaceTokenAccount>,
     
-    #[account(mut, token::mint = USDC_MINT, token::authority = gardener)]
+    #[account(mut, token::mint = config.usdc_mint, token::authority = gardener)]
     pub gardener_usdc_account: InterfaceAccount<'info, InterfaceTokenAccount>,
     
     pub token_program: Interface<'info, TokenInterface>,
@@ -757,22 +3498,110 @@ pub struct CollectMonthlyCommission<'info> {
 // === ACCOUNT DATA STRUCTURES ===
 
 #[account]
-#[derive(InitSpace)]
+#[derive(InitSpace, Default)]
 pub struct FundPool {
     pub total_shares: u64,
     pub optimized_nav: u64,        // Optimized NAV (one-directional Averaged NAV smoothed)
     pub real_nav: u64,             // live current portfolio value (for share pricing)
     pub total_users: u64,          // sum of all users
     pub pending_cashout: u64,      // sum of all current pending unstakes
-    pub authority: Pubkey,         // Gardener pubkey
+    pub pending_cashout_funded: u64, // USDC moved into the pending cashout vault and not yet paid out
+    pub total_cost_basis: u64,     // USDC paid by holders for all outstanding shares
+    pub authority: Pubkey,         // Fund admin (may update FundConfig)
+    pub last_commission_timestamp: i64,  // Last collect_monthly_commission run (0 = never)
+    pub high_water_nav_per_share: u64,   // Highest post-commission real NAV per share charged so far
+    pub last_fee_accrual_timestamp: i64, // Last management fee accrual
+    pub redemption_window_start: i64,    // Start of the current redemption gate window
+    pub redemption_window_capacity: u64, // USDC redeemable per window, snapshotted at window start
+    pub redemption_window_queued: u64,   // USDC requested from this window onward (incl. deferred)
+    pub last_nav_update_timestamp: i64,  // When real NAV was last repriced
+    pub pending_nav_value: u64,          // Portfolio value awaiting confirm_nav_update
+    pub pending_nav_timestamp: i64,      // When it was proposed (0 = none)
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct FundConfig {
+    pub gardener: Pubkey,                // Lambda key for NAV updates and commission collection
+    pub trading_wallet: Pubkey,          // Off-chain trading desk wallet funded from the trading vault
+    pub compliance_authority: Pubkey,    // Approves and revokes AllowlistEntry attestations
+    pub nav_confirm_authority: Pubkey,   // Second key confirming NAV moves beyond max_nav_change_bps
+    pub usdc_mint: Pubkey,               // Deposit / payout mint
+    pub unstake_lockup_seconds: i64,     // Notice period between initiate_unstake and complete_unstake
+    pub min_holding_seconds: i64,        // Minimum time after stake_timestamp before initiate_unstake
+    pub commission_bps: u16,             // Performance fee on profit (200 = 2%)
+    pub commission_period_seconds: i64,  // Minimum time between commission collections
+    pub management_fee_bps: u16,         // Annual management fee, accrued as dilutive shares
+    pub exit_fee_bps: u16,               // Fee on instant_unstake payouts, retained by the fund
+    pub nav_window_seconds: i64,         // Averaging window for optimized NAV
+    pub redemption_gate_bps: u16,        // Max share of real NAV redeemable per window (0 = no gate)
+    pub redemption_window_seconds: i64,  // Length of a redemption gate window
+    pub max_tvl_usdc: u64,               // Fund-wide real NAV cap on deposits (0 = unlimited)
+    pub min_deposit_usdc: u64,           // Smallest accepted stake_usdc amount
+    pub max_position_usdc: u64,          // Per-wallet position cap at real NAV (0 = unlimited)
+    pub allowlist_required: bool,        // stake_usdc requires an unrevoked AllowlistEntry
+    pub nav_quorum: u8,                  // Agreeing reporters needed to finalize NAV (0 = gardener updates)
+    pub nav_tolerance_bps: u16,          // Max deviation from the median for a report to agree
+    pub min_nav_update_interval_seconds: i64, // Minimum gap between NavHistory entries
+    pub max_nav_change_bps: u16,         // Larger moves need nav_confirm_authority (0 = unbounded)
+    pub max_nav_staleness_seconds: i64,  // stake / unstake refuse older NAV (0 = disabled)
+    pub oracle_max_age_seconds: i64,     // Oldest oracle price compute_nav accepts
+    pub price_feed_count: u8,            // Registered PriceFeedConfig accounts compute_nav must cover
+    pub max_users_per_registry: u64,     // Capacity of each UserRegistry
+    pub max_registries: u64,             // Number of UserRegistry PDAs allowed
+    pub pending_authority: Option<PendingAuthority>, // In-flight two-step rotation
+    pub pause_flags: u8,                 // Bitmask of PAUSE_* operations currently halted
     pub bump: u8,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PendingAuthority {
+    pub role: AuthorityRole,           // Which key is being rotated
+    pub new_authority: Pubkey,         // Must sign accept_authority
+    pub effective_timestamp: i64,      // Timelock expiry
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum AuthorityRole {
+    Admin,                             // FundPool.authority
+    Gardener,                          // FundConfig.gardener
+    TradingWallet,                     // FundConfig.trading_wallet
+    Compliance,                        // FundConfig.compliance_authority
+    NavConfirmer,                      // FundConfig.nav_confirm_authority
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FundConfigParams {
+    pub unstake_lockup_seconds: i64,
+    pub min_holding_seconds: i64,
+    pub commission_bps: u16,
+    pub commission_period_seconds: i64,
+    pub management_fee_bps: u16,
+    pub exit_fee_bps: u16,
+    pub nav_window_seconds: i64,
+    pub redemption_gate_bps: u16,
+    pub redemption_window_seconds: i64,
+    pub max_tvl_usdc: u64,
+    pub min_deposit_usdc: u64,
+    pub max_position_usdc: u64,
+    pub allowlist_required: bool,
+    pub nav_quorum: u8,
+    pub nav_tolerance_bps: u16,
+    pub min_nav_update_interval_seconds: i64,
+    pub max_nav_change_bps: u16,
+    pub max_nav_staleness_seconds: i64,
+    pub oracle_max_age_seconds: i64,
+    pub max_users_per_registry: u64,
+    pub max_registries: u64,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct NavHistory {
     #[max_len(84)]
     pub entries: Vec<NavHistoryEntry>, // 7 days of 2-hour intervals
+    pub bump: u8,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
@@ -781,17 +3610,74 @@ pub struct NavHistoryEntry {
     pub real_nav: u64,
 }
 
+#[account]
+#[derive(InitSpace)]
+pub struct NavReporters {
+    #[max_len(MAX_NAV_REPORTERS)]
+    pub reporters: Vec<Pubkey>,        // Keys allowed to submit_nav
+    pub current_epoch: u64,            // Epoch accepting submissions
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct NavSubmission {
+    pub epoch: u64,
+    #[max_len(MAX_NAV_REPORTERS)]
+    pub reports: Vec<NavReport>,       // One per reporter (latest wins)
+    pub finalized: bool,               // Applied to the fund by finalize_nav
+    pub finalized_value: u64,          // Median applied as real NAV
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeedConfig {
+    pub mint: Pubkey,                  // Trading wallet holding priced by this feed
+    pub kind: OracleKind,              // Which PriceOracle reads it
+    pub feed_id: [u8; 32],             // Pyth price feed id (unused for Par / Mock)
+    pub token_decimals: u8,            // Mint decimals, captured at registration
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum OracleKind {
+    Par,                               // Valued 1:1 in USDC (stablecoins)
+    Pyth,                              // Pyth PriceUpdateV2 account
+    Mock,                              // Program-owned MockPriceFeed (mock-oracle builds only)
+}
+
+#[cfg(feature = "mock-oracle")]
+#[account]
+#[derive(InitSpace)]
+pub struct MockPriceFeed {
+    pub mint: Pubkey,
+    pub price: i64,                    // USD price = price * 10^expo
+    pub expo: i32,
+    pub publish_time: i64,             // Last set_mock_price
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct NavReport {
+    pub reporter: Pubkey,
+    pub portfolio_value: u64,
+    pub timestamp: i64,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct PendingCashoutPool {
     #[max_len(100)]
     pub users: Vec<PendingUsers>, // Vec of users with pending unstakes
+    pub bump: u8,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
 pub struct PendingUsers {
     pub user: Pubkey,              // user pubkey
     pub pending_usdc_cashout: u64, // USDC amount
+    pub nonce: u64,                // UnstakeTicket nonce
 }
 
 #[account]
@@ -799,10 +3685,38 @@ pub struct PendingUsers {
 pub struct UserShare {
     pub user: Pubkey,                 // User's wallet pubkey
     pub shares: u64,                  // Number of shares owned
-    pub stake_timestamp: i64,         // Most recent stake timestamp
+    pub stake_timestamp: i64,         // When they last staked (start of the holding period)
     pub registry_id: u64,             // Which registry they're in (for 100k scaling)
     pub registry_index: u64,          // Position in UserRegistry.users
-    pub unstake_initiated: bool,      // boolean to show if unstake already initiated
+    pub pending_tickets: u64,         // Number of open UnstakeTicket PDAs
+    pub cost_basis_per_share: u64,    // Weighted-average USDC paid per share (NAV_PRECISION)
+    pub next_ticket_nonce: u64,       // Nonce for the next UnstakeTicket PDA
+    pub withdrawal_destination: Pubkey, // Default payout account for initiate_unstake when none is passed
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UnstakeTicket {
+    pub user: Pubkey,                 // Owner of the withdrawal request
+    pub nonce: u64,                   // Seed component, from UserShare.next_ticket_nonce
+    pub shares: u64,                  // Shares removed from the fund
+    pub usdc_value: u64,              // USDC value locked at initiation
+    pub initiated_timestamp: i64,     // When the request was made
+    pub maturity_timestamp: i64,      // Earliest complete_unstake time
+    pub destination: Pubkey,          // USDC token account complete_unstake pays
+    pub release_window_start: i64,    // Redemption gate window the request was released in (0 = ungated)
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct AllowlistEntry {
+    pub user: Pubkey,                 // Approved wallet
+    pub approved_by: Pubkey,          // Compliance authority that attested
+    pub approved_timestamp: i64,      // Most recent approval
+    pub revoked: bool,                // Blocks new deposits; withdrawals remain open
+    pub revoked_timestamp: i64,       // When revoked (0 = active)
+    pub bump: u8,
 }
 
 #[account]
@@ -810,6 +3724,7 @@ pub struct UserShare {
 pub struct RegistryDirectory {
     pub total_registries: u64,        // Number of active registries
     pub active_users: u64,            // Total active users across all registries
+    pub bump: u8,
 }
 
 #[account]
@@ -825,7 +3740,8 @@ pub struct UserRegistry {
 pub struct UserInfo {
     pub user_pubkey: Pubkey,                      // User's Public key, serving as unique identifier
     pub shares: u64,                              // Number of shares owned
-    pub stake_timestamp: i64,                     // When they first staked
+    pub cost_basis_per_share: u64,                // Mirrors UserShare.cost_basis_per_share
+    pub stake_timestamp: i64,                     // Mirrors UserShare.stake_timestamp
     pub unstake_initialized_timestamp: Option<i64>, // optional entry defaulted to none
     pub unstake_shares: Option<u64>,              // optional entry defaulted to none
     pub unstake_usdc_value: Option<u64>,          // optional entry defaulted to none
@@ -835,7 +3751,7 @@ pub struct UserInfo {
 pub enum ErrorCode {
     #[msg("Math operation resulted in overflow")]
     MathOverflow,
-    #[msg("Minimum 7-day lockup period not met")]
+    #[msg("Minimum holding period not met")]
     MinimumLockupNotMet,
     #[msg("No shares owned")]
     NoShares,
@@ -849,9 +3765,9 @@ pub enum ErrorCode {
     UnstakeAlreadyPending,
     #[msg("No unstake request pending")]
     NoUnstakePending,
-    #[msg("Unstake not ready - 7 day period not complete")]
+    #[msg("Unstake not ready - notice period not complete")]
     UnstakeNotReady,
-    #[msg("Update too frequent - minimum 2 hour interval required")]
+    #[msg("Update too frequent - minimum NAV update interval not elapsed")]
     UpdateTooFrequent,
     #[msg("Registry is full - cannot add more users")]
     RegistryFull,
@@ -861,6 +3777,258 @@ pub enum ErrorCode {
     UserNotInRegistry,
     #[msg("NAV history is empty")]
     EmptyNavHistory,
-    #[msg("Insufficient funds in trading wallet for commission")]
+    #[msg("Insufficient funds in trading vault")]
     InsufficientFunds,
-}
\ No newline at end of file
+    #[msg("Only the fund authority can perform this action")]
+    UnauthorizedAuthority,
+    #[msg("Invalid fund config parameters")]
+    InvalidConfig,
+    #[msg("No authority rotation pending")]
+    NoPendingAuthority,
+    #[msg("Signer does not match the proposed authority")]
+    InvalidPendingAuthority,
+    #[msg("Authority rotation timelock has not expired")]
+    AuthorityTimelockActive,
+    #[msg("Fund operation is paused")]
+    FundPaused,
+    #[msg("Commission already collected for this period")]
+    CommissionPeriodNotElapsed,
+    #[msg("Unstake has matured - complete it instead of cancelling")]
+    UnstakeMatured,
+    #[msg("Share amount must be greater than zero and not exceed shares owned")]
+    InvalidShareAmount,
+    #[msg("Shares already issued - share mint must be created before the first stake")]
+    SharesAlreadyIssued,
+    #[msg("Transfer hook invoked outside of a token transfer")]
+    NotTransferring,
+    #[msg("Transfer exceeds active shares - shares may be locked in a pending unstake")]
+    SharesLocked,
+    #[msg("Recipient has no active share position")]
+    RecipientNotRegistered,
+    #[msg("Recipient must differ from sender")]
+    InvalidRecipient,
+    #[msg("Destination does not match the account registered for this unstake")]
+    InvalidDestination,
+    #[msg("Invalid remaining accounts for batch settlement")]
+    InvalidRemainingAccounts,
+    #[msg("Liquidity buffer cannot cover this instant unstake")]
+    InsufficientLiquidityBuffer,
+    #[msg("Deposit would exceed the fund TVL cap")]
+    DepositCapExceeded,
+    #[msg("Deposit is below the fund minimum")]
+    DepositBelowMinimum,
+    #[msg("Position would exceed the per-wallet maximum")]
+    PositionLimitExceeded,
+    #[msg("Only the compliance authority can perform this action")]
+    UnauthorizedCompliance,
+    #[msg("Wallet is not on the fund allowlist")]
+    NotAllowlisted,
+    #[msg("Wallet allowlist approval has been revoked")]
+    AllowlistRevoked,
+    #[msg("NAV is set by reporter quorum - use submit_nav and finalize_nav")]
+    NavQuorumRequired,
+    #[msg("Signer is not a registered NAV reporter")]
+    UnauthorizedNavReporter,
+    #[msg("NAV epoch does not match the current epoch")]
+    NavEpochMismatch,
+    #[msg("Not enough agreeing NAV reports to reach quorum")]
+    NavQuorumNotReached,
+    #[msg("NAV epoch already finalized")]
+    NavEpochFinalized,
+    #[msg("NAV is stale - awaiting a fresh update")]
+    StaleNav,
+    #[msg("No NAV update awaiting confirmation")]
+    NoPendingNavUpdate,
+    #[msg("Pending NAV value does not match the expected value")]
+    PendingNavMismatch,
+    #[msg("Only the NAV confirm authority can perform this action")]
+    UnauthorizedNavConfirmer,
+    #[msg("Price account does not match the registered feed")]
+    InvalidPriceAccount,
+    #[msg("Oracle price must be positive")]
+    InvalidOraclePrice,
+    #[msg("Oracle price is too old")]
+    StaleOraclePrice,
+    #[msg("Every registered price feed must be included in compute_nav")]
+    IncompleteHoldings,
+    #[msg("A newer NAV has been applied since this value was parked")]
+    SupersededNavUpdate,
+    #[msg("Mock price feeds are not available in this build")]
+    MockOracleDisabled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_cost_basis_blends_purchases() {
+        // First purchase sets the basis
+        assert_eq!(calculate_weighted_cost_basis(0, 0, 100_000_000, 100_000_000).unwrap(), 1_000_000);
+        // 100 shares at $1.00 plus 100 shares for $200 averages to $1.50
+        assert_eq!(calculate_weighted_cost_basis(100_000_000, 1_000_000, 100_000_000, 200_000_000).unwrap(), 1_500_000);
+        // Zero-cost fee shares dilute the basis
+        assert_eq!(calculate_weighted_cost_basis(100_000_000, 1_000_000, 25_000_000, 0).unwrap(), 800_000);
+        // Nothing held and nothing bought has no basis
+        assert!(calculate_weighted_cost_basis(0, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn management_fee_shares_leave_holders_with_one_minus_fee() {
+        // 2% for a full year: S * 0.02 / 0.98
+        let fee_shares = calculate_management_fee_shares(1_000_000_000, 200, SECONDS_PER_YEAR).unwrap();
+        assert_eq!(fee_shares, 20_408_163);
+        let holder_fraction = 1_000_000_000u128 * BPS_DENOMINATOR as u128 / (1_000_000_000 + fee_shares) as u128;
+        assert_eq!(holder_fraction, 9_800);
+
+        // Half a year accrues roughly half the fee
+        assert_eq!(calculate_management_fee_shares(1_000_000_000, 200, SECONDS_PER_YEAR / 2).unwrap(), 10_101_010);
+    }
+
+    #[test]
+    fn management_fee_shares_skip_empty_periods() {
+        assert_eq!(calculate_management_fee_shares(1_000_000_000, 200, 0).unwrap(), 0);
+        assert_eq!(calculate_management_fee_shares(1_000_000_000, 200, -60).unwrap(), 0);
+        assert_eq!(calculate_management_fee_shares(1_000_000_000, 0, SECONDS_PER_YEAR).unwrap(), 0);
+        assert_eq!(calculate_management_fee_shares(0, 200, SECONDS_PER_YEAR).unwrap(), 0);
+        // A 100% fee fraction has no finite share count
+        assert!(calculate_management_fee_shares(1_000_000_000, 10_000, SECONDS_PER_YEAR).is_err());
+    }
+
+    fn gated_config() -> FundConfig {
+        FundConfig {
+            redemption_gate_bps: 1_000,
+            redemption_window_seconds: 86_400,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn redemption_gate_disabled_releases_immediately() {
+        let mut fund_pool = FundPool { real_nav: 1_000_000_000, ..Default::default() };
+        let config = FundConfig { redemption_window_seconds: 86_400, ..Default::default() };
+
+        assert_eq!(apply_redemption_gate(&mut fund_pool, &config, 900_000_000, 1_000_000).unwrap(), (1_000_000, 0));
+        assert_eq!(fund_pool.redemption_window_queued, 0);
+    }
+
+    #[test]
+    fn redemption_gate_defers_requests_over_capacity() {
+        let mut fund_pool = FundPool { real_nav: 1_000_000_000, ..Default::default() };
+        let config = gated_config();
+
+        // Rolls forward to the window containing t = 1_000_000 with 10% of real NAV as capacity
+        assert_eq!(apply_redemption_gate(&mut fund_pool, &config, 60_000_000, 1_000_000).unwrap(), (1_000_000, 950_400));
+        assert_eq!(fund_pool.redemption_window_start, 950_400);
+        assert_eq!(fund_pool.redemption_window_capacity, 100_000_000);
+
+        // The second request's last dollar falls into the next window
+        assert_eq!(apply_redemption_gate(&mut fund_pool, &config, 60_000_000, 1_000_000).unwrap(), (1_036_800, 1_036_800));
+        assert_eq!(fund_pool.redemption_window_queued, 120_000_000);
+    }
+
+    #[test]
+    fn redemption_gate_drains_queue_as_windows_pass() {
+        let mut fund_pool = FundPool { real_nav: 1_000_000_000, ..Default::default() };
+        let config = gated_config();
+        apply_redemption_gate(&mut fund_pool, &config, 120_000_000, 1_000_000).unwrap();
+
+        // Two windows later the backlog is gone and the request is released at once
+        fund_pool.real_nav = 500_000_000;
+        assert_eq!(apply_redemption_gate(&mut fund_pool, &config, 10_000_000, 1_123_200).unwrap(), (1_123_200, 1_123_200));
+        assert_eq!(fund_pool.redemption_window_start, 1_123_200);
+        assert_eq!(fund_pool.redemption_window_capacity, 50_000_000);
+        assert_eq!(fund_pool.redemption_window_queued, 10_000_000);
+    }
+
+    #[test]
+    fn median_of_odd_and_even_sets() {
+        assert_eq!(calculate_median(&mut [5, 1, 3]).unwrap(), 3);
+        assert_eq!(calculate_median(&mut [4, 1, 3, 2]).unwrap(), 2);
+        assert_eq!(calculate_median(&mut [7]).unwrap(), 7);
+        // Even-length averaging does not overflow
+        assert_eq!(calculate_median(&mut [u64::MAX, u64::MAX]).unwrap(), u64::MAX);
+        assert!(calculate_median(&mut []).is_err());
+    }
+
+    #[test]
+    fn tolerance_band_is_inclusive() {
+        assert!(is_within_tolerance(1_050, 1_000, 500));
+        assert!(is_within_tolerance(950, 1_000, 500));
+        assert!(!is_within_tolerance(1_051, 1_000, 500));
+        assert!(!is_within_tolerance(949, 1_000, 500));
+        assert!(is_within_tolerance(1_000, 1_000, 0));
+        assert!(!is_within_tolerance(1, 0, 10_000));
+    }
+
+    // PriceUpdateV2: discriminator, write_authority, verification_level, PriceFeedMessage
+    fn pyth_price_update(feed_id: [u8; 32], verification_level: u8, price: i64, expo: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = PYTH_PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[7u8; 32]);
+        data.push(verification_level);
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&42u64.to_le_bytes()); // conf
+        data.extend_from_slice(&expo.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&(publish_time - 1).to_le_bytes()); // prev_publish_time
+        data.extend_from_slice(&price.to_le_bytes()); // ema_price
+        data.extend_from_slice(&42u64.to_le_bytes()); // ema_conf
+        data.extend_from_slice(&1_000u64.to_le_bytes()); // posted_slot
+        data
+    }
+
+    #[test]
+    fn pyth_price_update_parses_feed_message() {
+        let feed_id = [3u8; 32];
+        let data = pyth_price_update(feed_id, PYTH_VERIFICATION_FULL, 15_012_345_678, -8, 1_700_000_000);
+
+        let price = parse_pyth_price_update(&data, &feed_id).unwrap();
+        assert_eq!(price.price, 15_012_345_678);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_700_000_000);
+    }
+
+    #[test]
+    fn pyth_price_update_rejects_mismatched_or_partial_updates() {
+        let feed_id = [3u8; 32];
+        let data = pyth_price_update(feed_id, PYTH_VERIFICATION_FULL, 100, -2, 1_700_000_000);
+
+        // Price for a different feed
+        assert!(parse_pyth_price_update(&data, &[4u8; 32]).is_err());
+        // Partially verified update
+        let partial = pyth_price_update(feed_id, 0, 100, -2, 1_700_000_000);
+        assert!(parse_pyth_price_update(&partial, &feed_id).is_err());
+        // Some other account type
+        let mut wrong_type = data.clone();
+        wrong_type[0] ^= 0xff;
+        assert!(parse_pyth_price_update(&wrong_type, &feed_id).is_err());
+        // Truncated message
+        assert!(parse_pyth_price_update(&data[..PYTH_VERIFICATION_LEVEL_OFFSET + 1 + 40], &feed_id).is_err());
+    }
+
+    #[test]
+    fn holding_value_scales_price_and_decimals() {
+        // 2 tokens with 9 decimals at $150.00000000 (expo -8)
+        let price = OraclePrice { price: 15_000_000_000, expo: -8, publish_time: 0 };
+        assert_eq!(calculate_holding_value(2_000_000_000, &price, 9, 6).unwrap(), 300_000_000);
+
+        // Positive scale: 5 whole-unit tokens at $2
+        let price = OraclePrice { price: 2, expo: 0, publish_time: 0 };
+        assert_eq!(calculate_holding_value(5, &price, 0, 6).unwrap(), 10_000_000);
+
+        // Stablecoin at par keeps base units
+        let par = OraclePrice { price: 1, expo: 0, publish_time: 0 };
+        assert_eq!(calculate_holding_value(1_234_567, &par, 6, 6).unwrap(), 1_234_567);
+    }
+
+    #[test]
+    fn holding_value_rejects_bad_prices() {
+        let zero = OraclePrice { price: 0, expo: 0, publish_time: 0 };
+        assert!(calculate_holding_value(1, &zero, 6, 6).is_err());
+        let negative = OraclePrice { price: -1, expo: 0, publish_time: 0 };
+        assert!(calculate_holding_value(1, &negative, 6, 6).is_err());
+        let huge = OraclePrice { price: i64::MAX, expo: 10, publish_time: 0 };
+        assert!(calculate_holding_value(u64::MAX, &huge, 0, 6).is_err());
+    }
+}
@@ -1,108 +1,696 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
-import { ShrubFund } from "../target/types/shrub_fund";
-import { PublicKey, Keypair, SystemProgram, Transaction } from "@solana/web3.js";
-import { ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID, getAssociatedTokenAddressSync, createMint, createAssociatedTokenAccountInstruction, mintTo, createSetAuthorityInstruction, AuthorityType, getAccount } from "@solana/spl-token";
+import { Program, BN } from "@coral-xyz/anchor";
+import { ShrubFund } from "./target/types/shrub_fund";
+import { PublicKey, Keypair, Transaction, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
+  getAssociatedTokenAddressSync,
+  createAssociatedTokenAccountInstruction,
+  createTransferCheckedWithTransferHookInstruction,
+  createMint,
+  mintTo,
+  getAccount,
+} from "@solana/spl-token";
+import { assert } from "chai";
+
+const BPF_LOADER_UPGRADEABLE_PROGRAM_ID = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
 
 describe("shrub_fund", () => {
   const provider = anchor.AnchorProvider.env();
   anchor.setProvider(provider);
   const program = anchor.workspace.ShrubFund as Program<ShrubFund>;
   const user = provider.wallet;
+  const tradingWallet = Keypair.generate(); // Off-chain trading desk wallet
+  const alice = Keypair.generate(); // Second staker
+  const bob = Keypair.generate(); // Receives shares by transfer only
+  const carol = Keypair.generate(); // Opens a position by transfer under fund limits
+
+  const pda = (...seeds: (Buffer | Uint8Array)[]) =>
+    PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  const u64Seed = (value: number) => new BN(value).toArrayLike(Buffer, "le", 8);
+
+  const fundPool = pda(Buffer.from("optimized_fund_pool_v2"));
+  const userShare = pda(Buffer.from("user_share_v2"), user.publicKey.toBuffer());
+  const userRegistry = pda(Buffer.from("user_registry_v2"), u64Seed(0));
+  const shareMint = pda(Buffer.from("share_mint_v2"));
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  );
+
+  const registryDirectory = pda(Buffer.from("registry_directory_v2"));
+  const ticketPda = (owner: PublicKey, nonce: BN) =>
+    pda(Buffer.from("unstake_ticket_v2"), owner.toBuffer(), nonce.toArrayLike(Buffer, "le", 8));
+
+  // Wide-open limits so the flow only exercises share accounting; a short notice period
+  // lets tickets mature within the test run
+  const params = {
+    unstakeLockupSeconds: new BN(2),
+    minHoldingSeconds: new BN(0),
+    commissionBps: 200,
+    commissionPeriodSeconds: new BN(30 * 24 * 60 * 60),
+    managementFeeBps: 0,
+    exitFeeBps: 0,
+    navWindowSeconds: new BN(7 * 24 * 60 * 60),
+    redemptionGateBps: 0,
+    redemptionWindowSeconds: new BN(24 * 60 * 60),
+    maxTvlUsdc: new BN(0),
+    minDepositUsdc: new BN(1_000_000),
+    maxPositionUsdc: new BN(0),
+    allowlistRequired: false,
+    navQuorum: 0,
+    navToleranceBps: 0,
+    minNavUpdateIntervalSeconds: new BN(0),
+    maxNavChangeBps: 0,
+    maxNavStalenessSeconds: new BN(0),
+    oracleMaxAgeSeconds: new BN(60),
+    maxUsersPerRegistry: new BN(100),
+    maxRegistries: new BN(10),
+  };
 
   let usdcMint: PublicKey;
-  let shrbMint: PublicKey;
   let userUsdcAta: PublicKey;
-  let userShrbAta: PublicKey;
-  let vaultAuthority: PublicKey;
-  let vaultUsdcAta: PublicKey;
-  let mintAuthority: PublicKey;
-  let tradingWallet = Keypair.generate();  // Mock trading wallet for local test
-  let tradingUsdcAta: PublicKey;
+  let userShareAta: PublicKey;
 
-  before(async () => {
-    // Mock USDC mint (6 decimals like real USDC)
-    usdcMint = await createMint(provider.connection, user.payer, user.publicKey, null, 6);
+  // Fails unless the transaction is rejected with the given program error
+  const expectError = async (tx: Promise<unknown>, code: string) => {
+    try {
+      await tx;
+    } catch (err: any) {
+      const logs = (err.logs ?? err.transactionLogs ?? []).join("\n");
+      assert.include(`${err}\n${logs}`, code);
+      return;
+    }
+    assert.fail(`expected ${code}`);
+  };
+
+  // Block until the cluster clock reaches `timestamp`
+  const waitUntil = async (timestamp: number) => {
+    for (;;) {
+      const blockTime = await provider.connection.getBlockTime(await provider.connection.getSlot());
+      if (blockTime !== null && blockTime >= timestamp) return;
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+  };
+
+  // Apply a partial config change on top of the baseline params
+  const setConfig = (overrides: Partial<typeof params>) =>
+    program.methods
+      .updateFundConfig({ ...params, ...overrides })
+      .accountsPartial({ authority: user.publicKey })
+      .rpc();
 
-    // Mock SHRB mint (9 decimals, initially authority is user)
-    shrbMint = await createMint(provider.connection, user.payer, user.publicKey, null, 9);
+  // Queue an unstake of `shares` paid to the provider's USDC account; `owner` defaults to the provider wallet
+  const initiateUnstake = async (shares: BN, owner?: Keypair) => {
+    const ownerKey = owner?.publicKey ?? user.publicKey;
+    const { nextTicketNonce: nonce } = await program.account.userShare.fetch(
+      pda(Buffer.from("user_share_v2"), ownerKey.toBuffer())
+    );
+    const unstakeTicket = ticketPda(ownerKey, nonce);
+    const builder = program.methods.initiateUnstake(shares).accountsPartial({
+      user: ownerKey,
+      unstakeTicket,
+      destinationUsdcAccount: userUsdcAta,
+      userRegistry,
+      userShareTokenAccount: getAssociatedTokenAddressSync(shareMint, ownerKey, false, TOKEN_2022_PROGRAM_ID),
+      shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+    });
+    await (owner ? builder.signers([owner]).rpc() : builder.rpc());
+    return { nonce, unstakeTicket };
+  };
+
+  const cancelUnstake = (nonce: BN, unstakeTicket: PublicKey) =>
+    program.methods
+      .cancelUnstake(nonce)
+      .accountsPartial({
+        user: user.publicKey,
+        unstakeTicket,
+        userRegistry,
+        userShareTokenAccount: userShareAta,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .rpc();
+
+  const shareAta = (owner: PublicKey) =>
+    getAssociatedTokenAddressSync(shareMint, owner, false, TOKEN_2022_PROGRAM_ID);
+
+  // Fund a test wallet with SOL and open its Token-2022 share account
+  const onboard = async (wallet: Keypair) => {
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({ fromPubkey: user.publicKey, toPubkey: wallet.publicKey, lamports: LAMPORTS_PER_SOL }),
+        createAssociatedTokenAccountInstruction(
+          user.publicKey,
+          shareAta(wallet.publicKey),
+          wallet.publicKey,
+          shareMint,
+          TOKEN_2022_PROGRAM_ID
+        )
+      )
+    );
+  };
 
-    // Compute PDAs (match lib.rs seeds)
-    [mintAuthority] = PublicKey.findProgramAddressSync([Buffer.from("mint_authority")], program.programId);
-    [vaultAuthority] = PublicKey.findProgramAddressSync([Buffer.from("vault")], program.programId);
+  // Move `shares` between wallets through transfer_shares; `sender` defaults to the provider wallet
+  const transferShares = (recipient: PublicKey, shares: BN, sender?: Keypair, recipientAllowlistEntry?: PublicKey) => {
+    const senderKey = sender?.publicKey ?? user.publicKey;
+    const builder = program.methods.transferShares(shares).accountsPartial({
+      sender: senderKey,
+      recipient,
+      senderRegistry: userRegistry,
+      senderShareTokenAccount: shareAta(senderKey),
+      recipientShareTokenAccount: shareAta(recipient),
+      shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+      registryDirectory,
+      userRegistry,
+      recipientAllowlistEntry: recipientAllowlistEntry ?? null,
+    });
+    return sender ? builder.signers([sender]).rpc() : builder.rpc();
+  };
 
-    // Set SHRB mint authority to PDA (simulate transfer)
-    const setAuthIx = createSetAuthorityInstruction(
-      shrbMint,
-      user.publicKey,
-      AuthorityType.MintTokens,
-      mintAuthority
+  // Plain Token-2022 transfer_checked; the share hook resolves its extra accounts on-chain
+  const hookTransfer = async (recipient: PublicKey, amount: number, sender?: Keypair) => {
+    const senderKey = sender?.publicKey ?? user.publicKey;
+    const ix = await createTransferCheckedWithTransferHookInstruction(
+      provider.connection,
+      shareAta(senderKey),
+      shareMint,
+      shareAta(recipient),
+      senderKey,
+      BigInt(amount),
+      6,
+      [],
+      "confirmed",
+      TOKEN_2022_PROGRAM_ID
     );
-    const tx = new Transaction().add(setAuthIx);
-    await provider.sendAndConfirm(tx, [user.payer]);
+    return provider.sendAndConfirm(new Transaction().add(ix), sender ? [sender] : []);
+  };
 
-    // Create and fund user USDC ATA
+  const registryEntry = async (wallet: PublicKey) => {
+    const registry = await program.account.userRegistry.fetch(userRegistry);
+    return registry.users.find((info) => info !== null && info.userPubkey.equals(wallet));
+  };
+
+  before(async () => {
+    // Mock USDC mint (6 decimals like real USDC) funded with 1,000 USDC
+    usdcMint = await createMint(provider.connection, user.payer, user.publicKey, null, 6);
     userUsdcAta = getAssociatedTokenAddressSync(usdcMint, user.publicKey);
-    const createUserUsdcAtaIx = createAssociatedTokenAccountInstruction(
-      user.publicKey,
-      userUsdcAta,
-      user.publicKey,
-      usdcMint,
-      TOKEN_PROGRAM_ID,
-      ASSOCIATED_TOKEN_PROGRAM_ID
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(user.publicKey, userUsdcAta, user.publicKey, usdcMint)
+      )
     );
-    await provider.sendAndConfirm(new Transaction().add(createUserUsdcAtaIx), [user.payer]);
-    await mintTo(provider.connection, user.payer, usdcMint, userUsdcAta, user.publicKey, 1000000);  // 1 USDC raw (6 decimals)
-
-    // User SHRB ATA (will be init_if_needed in instruction)
-    userShrbAta = getAssociatedTokenAddressSync(shrbMint, user.publicKey);
-
-    // Vault USDC ATA (PDA-owned, init_if_needed in instruction)
-    vaultUsdcAta = getAssociatedTokenAddressSync(usdcMint, vaultAuthority, true);
-
-    // Trading USDC ATA (create for mock wallet)
-    tradingUsdcAta = getAssociatedTokenAddressSync(usdcMint, tradingWallet.publicKey);
-    const createTradingAtaIx = createAssociatedTokenAccountInstruction(
-      user.publicKey,
-      tradingUsdcAta,
-      tradingWallet.publicKey,
-      usdcMint,
-      TOKEN_PROGRAM_ID,
-      ASSOCIATED_TOKEN_PROGRAM_ID
+    await mintTo(provider.connection, user.payer, usdcMint, userUsdcAta, user.publicKey, 1_000_000_000);
+
+    // The provider wallet deployed the program, so it may bootstrap the fund; it also acts as gardener
+    await program.methods
+      .initializeFund(user.publicKey, tradingWallet.publicKey, params)
+      .accountsPartial({ authority: user.publicKey, programData, usdcMint })
+      .rpc();
+    await program.methods
+      .initializeTradingVault()
+      .accountsPartial({ authority: user.publicKey, usdcMint, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+    await program.methods
+      .initializePendingCashoutVault()
+      .accountsPartial({ authority: user.publicKey, usdcMint, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+    await program.methods
+      .initializeLiquidityBufferVault()
+      .accountsPartial({ authority: user.publicKey, usdcMint, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+    await program.methods
+      .initializeShareMint()
+      .accountsPartial({ authority: user.publicKey, shareTokenProgram: TOKEN_2022_PROGRAM_ID })
+      .rpc();
+    await program.methods.initializeShareHook().accountsPartial({ authority: user.publicKey }).rpc();
+    await program.methods.initializeUserRegistry(new BN(0)).accountsPartial({ authority: user.publicKey }).rpc();
+
+    // Share tokens live in a Token-2022 associated account
+    userShareAta = getAssociatedTokenAddressSync(shareMint, user.publicKey, false, TOKEN_2022_PROGRAM_ID);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          user.publicKey,
+          userShareAta,
+          user.publicKey,
+          shareMint,
+          TOKEN_2022_PROGRAM_ID
+        )
+      )
     );
-    await provider.sendAndConfirm(new Transaction().add(createTradingAtaIx), [user.payer]);
   });
 
-  it("Contributes USDC and mints SHRB", async () => {
-    const amount = 1000000n;  // 1 USDC raw (6 decimals; program mints 1:1 raw SHRB)
+  it("Stakes USDC and mints matching share tokens", async () => {
+    const usdcAmount = new BN(100_000_000); // 100 USDC
 
     await program.methods
-      .contribute(amount)
-      .accounts({
+      .stakeUsdc(usdcAmount)
+      .accountsPartial({
         user: user.publicKey,
         userUsdcAccount: userUsdcAta,
-        userShrbAccount: userShrbAta,
-        shrbMint: shrbMint,
-        mintAuthority: mintAuthority,
-        vaultUsdcAccount: vaultUsdcAta,
-        vaultAuthority: vaultAuthority,
-        tradingWallet: tradingWallet.publicKey,
-        tradingUsdcAccount: tradingUsdcAta,
-        usdcMint: usdcMint,
+        userShareTokenAccount: userShareAta,
+        shareTokenProgram: TOKEN_2022_PROGRAM_ID,
         tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
-        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        registryDirectory,
+        userRegistry,
+        allowlistEntry: null,
       })
       .rpc();
 
-    // Verify balances after contribution
-    const userShrbAccount = await getAccount(provider.connection, userShrbAta);
-    const tradingUsdcAccount = await getAccount(provider.connection, tradingUsdcAta);
+    // First stake prices at $1.00, so shares match USDC base units
+    const share = await program.account.userShare.fetch(userShare);
+    const pool = await program.account.fundPool.fetch(fundPool);
+    const shareTokens = await getAccount(provider.connection, userShareAta, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(share.shares.toString(), usdcAmount.toString());
+    assert.equal(shareTokens.amount.toString(), share.shares.toString());
+    assert.equal(pool.totalShares.toString(), share.shares.toString());
+    assert.equal(pool.realNav.toString(), usdcAmount.toString());
+    assert.equal(pool.optimizedNav.toString(), usdcAmount.toString());
+    assert.equal(pool.totalUsers.toNumber(), 1);
+
+    const registry = await program.account.userRegistry.fetch(userRegistry);
+    assert.equal(registry.userCount.toNumber(), 1);
+    assert.ok(registry.users[share.registryIndex.toNumber()].userPubkey.equals(user.publicKey));
+  });
+
+  it("Initiates an unstake, burning the redeemed share tokens", async () => {
+    const shares = new BN(40_000_000);
+    const before = await program.account.userShare.fetch(userShare);
+    const unstakeTicket = ticketPda(user.publicKey, before.nextTicketNonce);
+
+    await program.methods
+      .initiateUnstake(shares)
+      .accountsPartial({
+        user: user.publicKey,
+        unstakeTicket,
+        destinationUsdcAccount: userUsdcAta,
+        userRegistry,
+        userShareTokenAccount: userShareAta,
+        shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .rpc();
+
+    const share = await program.account.userShare.fetch(userShare);
+    const pool = await program.account.fundPool.fetch(fundPool);
+    const ticket = await program.account.unstakeTicket.fetch(unstakeTicket);
+    const shareTokens = await getAccount(provider.connection, userShareAta, undefined, TOKEN_2022_PROGRAM_ID);
+
+    // Token balance and share accounting drop together
+    assert.equal(share.shares.toString(), before.shares.sub(shares).toString());
+    assert.equal(shareTokens.amount.toString(), share.shares.toString());
+    assert.equal(pool.totalShares.toString(), share.shares.toString());
+    assert.equal(share.pendingTickets.toNumber(), 1);
+    assert.ok(share.withdrawalDestination.equals(userUsdcAta));
+
+    // Value is locked at $1.00 and paid out after the notice period
+    assert.equal(ticket.shares.toString(), shares.toString());
+    assert.equal(ticket.usdcValue.toString(), shares.toString());
+    assert.ok(ticket.destination.equals(userUsdcAta));
+    assert.equal(
+      ticket.maturityTimestamp.sub(ticket.initiatedTimestamp).toNumber(),
+      params.unstakeLockupSeconds.toNumber()
+    );
+    assert.equal(pool.pendingCashout.toString(), shares.toString());
+    assert.equal(pool.optimizedNav.toString(), pool.realNav.toString());
+  });
+
+  it("Completes the matured ticket and pays the locked USDC", async () => {
+    const nonce = new BN(0);
+    const unstakeTicket = ticketPda(user.publicKey, nonce);
+    const ticket = await program.account.unstakeTicket.fetch(unstakeTicket);
+
+    // The gardener moves the locked value out of the trading vault
+    await program.methods
+      .fundPendingCashouts(null)
+      .accountsPartial({ gardener: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+
+    const usdcBefore = (await getAccount(provider.connection, userUsdcAta)).amount;
+    await waitUntil(ticket.maturityTimestamp.toNumber() + 1);
+    await program.methods
+      .completeUnstake(nonce)
+      .accountsPartial({
+        cranker: user.publicKey,
+        user: user.publicKey,
+        unstakeTicket,
+        destinationUsdcAccount: userUsdcAta,
+        userRegistry,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    const usdcAfter = (await getAccount(provider.connection, userUsdcAta)).amount;
+    assert.equal((usdcAfter - usdcBefore).toString(), ticket.usdcValue.toString());
+    assert.isNull(await program.account.unstakeTicket.fetchNullable(unstakeTicket));
+
+    const share = await program.account.userShare.fetch(userShare);
+    const pool = await program.account.fundPool.fetch(fundPool);
+    assert.equal(share.pendingTickets.toNumber(), 0);
+    assert.equal(pool.pendingCashout.toNumber(), 0);
+    assert.equal(pool.pendingCashoutFunded.toNumber(), 0);
+  });
+
+  it("Prices a second instant unstake off the NAV left by the first", async () => {
+    await setConfig({ exitFeeBps: 100 });
+    await program.methods
+      .fundLiquidityBuffer(new BN(20_000_000))
+      .accountsPartial({ gardener: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+
+    const shares = new BN(10_000_000);
+    const instantUnstake = () =>
+      program.methods
+        .instantUnstake(shares)
+        .accountsPartial({
+          user: user.publicKey,
+          userRegistry,
+          destinationUsdcAccount: userUsdcAta,
+          userShareTokenAccount: userShareAta,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .rpc();
+
+    await instantUnstake();
+
+    // The retained exit fee lifts the price paid to the next redeemer
+    const pool = await program.account.fundPool.fetch(fundPool);
+    const navPerShare = pool.optimizedNav.mul(new BN(1_000_000)).div(pool.totalShares);
+    const gross = shares.mul(navPerShare).div(new BN(1_000_000));
+    const expectedPayout = gross.sub(gross.muln(100).divn(10_000));
+    assert.equal(navPerShare.toNumber(), 1_002_000);
+    assert.equal(expectedPayout.toNumber(), 9_919_800);
+
+    const usdcBefore = (await getAccount(provider.connection, userUsdcAta)).amount;
+    await instantUnstake();
+    const usdcAfter = (await getAccount(provider.connection, userUsdcAta)).amount;
+    assert.equal((usdcAfter - usdcBefore).toString(), expectedPayout.toString());
+
+    const after = await program.account.fundPool.fetch(fundPool);
+    assert.equal(after.optimizedNav.toString(), pool.optimizedNav.sub(expectedPayout).toString());
+    assert.equal(after.realNav.toString(), pool.realNav.sub(expectedPayout).toString());
+
+    await setConfig({});
+  });
+
+  it("Cancels a pending unstake and restores the shares", async () => {
+    await setConfig({ unstakeLockupSeconds: new BN(3600) });
+    const before = await program.account.userShare.fetch(userShare);
+    const { nonce, unstakeTicket } = await initiateUnstake(new BN(10_000_000));
+
+    await cancelUnstake(nonce, unstakeTicket);
+
+    // Re-pricing at real NAV may round down, but never past the shares unstaked
+    const share = await program.account.userShare.fetch(userShare);
+    const shareTokens = await getAccount(provider.connection, userShareAta, undefined, TOKEN_2022_PROGRAM_ID);
+    assert.isAtMost(share.shares.toNumber(), before.shares.toNumber());
+    assert.isAtLeast(share.shares.toNumber(), before.shares.toNumber() - 1);
+    assert.equal(shareTokens.amount.toString(), share.shares.toString());
+    assert.equal(share.pendingTickets.toNumber(), before.pendingTickets.toNumber());
+    assert.isNull(await program.account.unstakeTicket.fetchNullable(unstakeTicket));
+
+    await setConfig({});
+  });
+
+  it("Rejects cancelling a matured unstake", async () => {
+    const { nonce, unstakeTicket } = await initiateUnstake(new BN(5_000_000));
+    const ticket = await program.account.unstakeTicket.fetch(unstakeTicket);
+    await waitUntil(ticket.maturityTimestamp.toNumber() + 1);
+
+    await expectError(cancelUnstake(nonce, unstakeTicket), "UnstakeMatured");
+    assert.isNotNull(await program.account.unstakeTicket.fetchNullable(unstakeTicket));
+  });
+
+  it("Settles matured tickets in a keeper batch", async () => {
+    // The ticket left behind by the matured-cancel test
+    const { nextTicketNonce } = await program.account.userShare.fetch(userShare);
+    const unstakeTicket = ticketPda(user.publicKey, nextTicketNonce.subn(1));
+    const ticket = await program.account.unstakeTicket.fetch(unstakeTicket);
+
+    await program.methods
+      .fundPendingCashouts(null)
+      .accountsPartial({ gardener: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .rpc();
+
+    const usdcBefore = (await getAccount(provider.connection, userUsdcAta)).amount;
+    await program.methods
+      .settleMaturedUnstakes()
+      .accountsPartial({ cranker: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+      .remainingAccounts(
+        [unstakeTicket, userShare, userRegistry, userUsdcAta, user.publicKey].map((pubkey) => ({
+          pubkey,
+          isSigner: false,
+          isWritable: true,
+        }))
+      )
+      .rpc();
+
+    const usdcAfter = (await getAccount(provider.connection, userUsdcAta)).amount;
+    assert.equal((usdcAfter - usdcBefore).toString(), ticket.usdcValue.toString());
+    assert.isNull(await program.account.unstakeTicket.fetchNullable(unstakeTicket));
+    assert.equal((await program.account.userShare.fetch(userShare)).pendingTickets.toNumber(), 0);
+  });
+
+  it("Rejects a settle batch with no tickets", async () => {
+    await expectError(
+      program.methods
+        .settleMaturedUnstakes()
+        .accountsPartial({ cranker: user.publicKey, tokenProgram: TOKEN_PROGRAM_ID })
+        .rpc(),
+      "InvalidRemainingAccounts"
+    );
+  });
+
+  it("Transfers shares to a new holder with the sender's cost basis", async () => {
+    // Alice stakes her own USDC; Bob only ever receives shares
+    await onboard(alice);
+    await onboard(bob);
+    const aliceUsdcAta = getAssociatedTokenAddressSync(usdcMint, alice.publicKey);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(user.publicKey, aliceUsdcAta, alice.publicKey, usdcMint)
+      )
+    );
+    await mintTo(provider.connection, user.payer, usdcMint, aliceUsdcAta, user.publicKey, 20_000_000);
+    await program.methods
+      .stakeUsdc(new BN(10_000_000))
+      .accountsPartial({
+        user: alice.publicKey,
+        userUsdcAccount: aliceUsdcAta,
+        userShareTokenAccount: shareAta(alice.publicKey),
+        shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        registryDirectory,
+        userRegistry,
+        allowlistEntry: null,
+      })
+      .signers([alice])
+      .rpc();
+
+    const shares = new BN(5_000_000);
+    const sender = await program.account.userShare.fetch(userShare);
+    await transferShares(bob.publicKey, shares);
+
+    const bobShare = await program.account.userShare.fetch(
+      pda(Buffer.from("user_share_v2"), bob.publicKey.toBuffer())
+    );
+    const bobTokens = await getAccount(provider.connection, shareAta(bob.publicKey), undefined, TOKEN_2022_PROGRAM_ID);
+    assert.ok(bobShare.user.equals(bob.publicKey));
+    assert.equal(bobShare.shares.toString(), shares.toString());
+    assert.equal(bobTokens.amount.toString(), shares.toString());
+    assert.equal(bobShare.stakeTimestamp.toString(), sender.stakeTimestamp.toString());
+    assert.equal(bobShare.costBasisPerShare.toString(), sender.costBasisPerShare.toString());
+
+    const entry = await registryEntry(bob.publicKey);
+    assert.isDefined(entry);
+    assert.equal(entry!.shares.toString(), shares.toString());
+    const senderAfter = await program.account.userShare.fetch(userShare);
+    assert.equal(senderAfter.shares.toString(), sender.shares.sub(shares).toString());
+    assert.equal((await registryEntry(user.publicKey))!.shares.toString(), senderAfter.shares.toString());
+  });
+
+  it("Rejects transfer_shares inside the sender's holding period", async () => {
+    await setConfig({ minHoldingSeconds: new BN(3600) });
+    await expectError(transferShares(bob.publicKey, new BN(1_000_000), alice), "MinimumLockupNotMet");
+    await setConfig({});
+  });
+
+  it("Rejects an opening transfer worth less than the minimum deposit", async () => {
+    await onboard(carol);
+    await expectError(transferShares(carol.publicKey, new BN(500_000)), "DepositBelowMinimum");
+  });
+
+  it("Enforces the position limit on the recipient of transfer_shares", async () => {
+    await setConfig({ maxPositionUsdc: new BN(10_000_000) });
+
+    // Bob already holds 5 shares, so 6 more puts him over $10
+    await expectError(transferShares(bob.publicKey, new BN(6_000_000)), "PositionLimitExceeded");
+
+    const before = await program.account.userShare.fetch(pda(Buffer.from("user_share_v2"), bob.publicKey.toBuffer()));
+    await transferShares(bob.publicKey, new BN(2_000_000));
+    const after = await program.account.userShare.fetch(pda(Buffer.from("user_share_v2"), bob.publicKey.toBuffer()));
+    assert.equal(after.shares.sub(before.shares).toNumber(), 2_000_000);
+
+    await setConfig({});
+  });
+
+  it("Requires an allowlisted recipient for transfer_shares when the allowlist is on", async () => {
+    await setConfig({ allowlistRequired: true });
+    const carolAllowlistEntry = pda(Buffer.from("allowlist_v2"), carol.publicKey.toBuffer());
+
+    await expectError(transferShares(carol.publicKey, new BN(2_000_000)), "NotAllowlisted");
+
+    await program.methods
+      .approveAllowlist(carol.publicKey)
+      .accountsPartial({ complianceAuthority: user.publicKey })
+      .rpc();
+    await transferShares(carol.publicKey, new BN(2_000_000), undefined, carolAllowlistEntry);
+    const carolShare = await program.account.userShare.fetch(
+      pda(Buffer.from("user_share_v2"), carol.publicKey.toBuffer())
+    );
+    assert.equal(carolShare.shares.toNumber(), 2_000_000);
+
+    await program.methods
+      .revokeAllowlist(carol.publicKey)
+      .accountsPartial({ complianceAuthority: user.publicKey })
+      .rpc();
+    await expectError(
+      transferShares(carol.publicKey, new BN(1_000_000), undefined, carolAllowlistEntry),
+      "AllowlistRevoked"
+    );
+
+    await setConfig({});
+  });
+
+  it("Blocks hook transfers while share transfers are paused", async () => {
+    await program.methods.setPauseFlags(32).accountsPartial({ authority: user.publicKey }).rpc();
+    await expectError(hookTransfer(bob.publicKey, 1_000_000), "FundPaused");
+    await program.methods.setPauseFlags(0).accountsPartial({ authority: user.publicKey }).rpc();
+  });
+
+  it("Blocks hook transfers inside the sender's holding period", async () => {
+    await setConfig({ minHoldingSeconds: new BN(3600) });
+    await expectError(hookTransfer(bob.publicKey, 1_000_000, alice), "MinimumLockupNotMet");
+    await setConfig({});
+  });
+
+  it("Blocks a hook transfer that opens a position below the minimum deposit", async () => {
+    // Carol queues her whole position: zero active shares, but her UserShare stays registered
+    const carolShareAccount = pda(Buffer.from("user_share_v2"), carol.publicKey.toBuffer());
+    const { shares } = await program.account.userShare.fetch(carolShareAccount);
+    await initiateUnstake(shares, carol);
+    const carolShare = await program.account.userShare.fetch(carolShareAccount);
+    assert.equal(carolShare.shares.toNumber(), 0);
+    assert.ok(carolShare.user.equals(carol.publicKey));
+
+    await expectError(hookTransfer(carol.publicKey, 500_000), "DepositBelowMinimum");
+  });
+
+  it("Moves shares through the hook and updates both positions", async () => {
+    const bobShareAccount = pda(Buffer.from("user_share_v2"), bob.publicKey.toBuffer());
+    const senderBefore = await program.account.userShare.fetch(userShare);
+    const bobBefore = await program.account.userShare.fetch(bobShareAccount);
+    const amount = 1_000_000;
+
+    await hookTransfer(bob.publicKey, amount);
+
+    const senderAfter = await program.account.userShare.fetch(userShare);
+    const bobAfter = await program.account.userShare.fetch(bobShareAccount);
+    assert.equal(senderAfter.shares.toString(), senderBefore.shares.subn(amount).toString());
+    assert.equal(bobAfter.shares.toString(), bobBefore.shares.addn(amount).toString());
+    // The receiver keeps its own holding period
+    assert.equal(bobAfter.stakeTimestamp.toString(), bobBefore.stakeTimestamp.toString());
+
+    const senderTokens = await getAccount(provider.connection, userShareAta, undefined, TOKEN_2022_PROGRAM_ID);
+    const bobTokens = await getAccount(provider.connection, shareAta(bob.publicKey), undefined, TOKEN_2022_PROGRAM_ID);
+    assert.equal(senderTokens.amount.toString(), senderAfter.shares.toString());
+    assert.equal(bobTokens.amount.toString(), bobAfter.shares.toString());
+
+    assert.equal((await registryEntry(user.publicKey))!.shares.toString(), senderAfter.shares.toString());
+    assert.equal((await registryEntry(bob.publicKey))!.shares.toString(), bobAfter.shares.toString());
+  });
+
+  it("Computes NAV from trading wallet holdings priced by a mock oracle", async () => {
+    // Trading wallet holds 2 tokens (9 decimals) priced at $150.000000
+    const holdingMint = await createMint(provider.connection, user.payer, user.publicKey, null, 9);
+    const holdingAta = getAssociatedTokenAddressSync(holdingMint, tradingWallet.publicKey);
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(user.publicKey, holdingAta, tradingWallet.publicKey, holdingMint)
+      )
+    );
+    await mintTo(provider.connection, user.payer, holdingMint, holdingAta, user.publicKey, 2_000_000_000);
+
+    const priceFeed = pda(Buffer.from("price_feed_v2"), holdingMint.toBuffer());
+    const mockPriceFeed = pda(Buffer.from("mock_price_feed_v2"), holdingMint.toBuffer());
+    await program.methods
+      .registerPriceFeed({ mock: {} }, Array(32).fill(0))
+      .accountsPartial({ authority: user.publicKey, mint: holdingMint })
+      .rpc();
+    await program.methods
+      .setMockPrice(new BN(150_000_000), -6)
+      .accountsPartial({ authority: user.publicKey, mint: holdingMint })
+      .rpc();
+
+    const before = await program.account.fundPool.fetch(fundPool);
+    const tradingVault = pda(Buffer.from("trading_vault_v2"));
+    const liquidityBufferVault = pda(Buffer.from("liquidity_buffer_vault_v2"));
+    const vaultBalance = (await getAccount(provider.connection, tradingVault)).amount;
+    const bufferBalance = (await getAccount(provider.connection, liquidityBufferVault)).amount;
+
+    await program.methods
+      .computeNav()
+      .accountsPartial({
+        gardener: user.publicKey,
+        usdcMint,
+        feeSettlement: {
+          payer: user.publicKey,
+          gardenerShareTokenAccount: userShareAta,
+          shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+          registryDirectory: null,
+          userRegistry: null,
+        },
+      })
+      .remainingAccounts([
+        { pubkey: holdingAta, isSigner: false, isWritable: false },
+        { pubkey: priceFeed, isSigner: false, isWritable: false },
+        { pubkey: mockPriceFeed, isSigner: false, isWritable: false },
+      ])
+      .rpc();
+
+    // Vault and buffer USDC + $300 of holdings, less locked unstakes that are not yet funded
+    const pool = await program.account.fundPool.fetch(fundPool);
+    const expected = new BN(vaultBalance.toString())
+      .add(new BN(bufferBalance.toString()))
+      .add(new BN(300_000_000))
+      .sub(before.pendingCashout.sub(before.pendingCashoutFunded));
+    assert.equal(pool.realNav.toString(), expected.toString());
+    assert.equal(pool.pendingNavValue.toNumber(), 0);
+  });
 
-    console.log("User SHRB balance after (raw):", userShrbAccount.amount.toString());  // Expected: '1000000'
-    console.log("User SHRB balance after (UI):", Number(userShrbAccount.amount) / 10**9);  // Expected: 0.001 (due to 9 decimals)
-    console.log("Trading USDC balance after (raw):", tradingUsdcAccount.amount.toString());  // Expected: '1000000'
-    console.log("Trading USDC balance after (UI):", Number(tradingUsdcAccount.amount) / 10**6);  // Expected: 1
-    console.log("Test passed - check transaction logs for 'Contribution processed' message!");
+  it("Rejects compute_nav from anyone but the gardener", async () => {
+    const outsider = Keypair.generate();
+    try {
+      await program.methods
+        .computeNav()
+        .accountsPartial({
+          gardener: outsider.publicKey,
+          usdcMint,
+          feeSettlement: {
+            payer: outsider.publicKey,
+            gardenerShareTokenAccount: userShareAta,
+            shareTokenProgram: TOKEN_2022_PROGRAM_ID,
+            registryDirectory: null,
+            userRegistry: null,
+          },
+        })
+        .signers([outsider])
+        .rpc();
+      assert.fail("compute_nav accepted a non-gardener signer");
+    } catch (err) {
+      assert.include(String(err), "UnauthorizedGardener");
+    }
   });
-});
\ No newline at end of file
+});